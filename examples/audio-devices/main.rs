@@ -0,0 +1,10 @@
+use kiyo::app::output_device_names;
+
+/*
+ * Lists the available audio output devices, use these names with AppConfig::audio_device
+ */
+fn main() {
+    for name in output_device_names() {
+        println!("{}", name);
+    }
+}
@@ -1,41 +1,27 @@
 use kiyo::app::app::{App, AppConfig};
 use kiyo::app::audio_orch::AudioConfig;
-use kiyo::app::draw_orch::{ClearConfig, DispatchConfig, DrawConfig, ImageConfig, Pass};
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
+use kiyo::app::pipeline::{SpecConstant, SpecValue};
+use kiyo::Error;
 
-fn main() {
+fn main() -> Result<(), Error> {
 
-    let app_config = AppConfig {
-        width: 1000,
-        height: 1000,
-        vsync: true,
-        log_fps: false,
-        fullscreen: false,
-    };
+    let app_config = AppConfig::default();
 
-    let config = DrawConfig {
-        images: Vec::from([
-            ImageConfig {
-                clear: ClearConfig::Color(0.0, 0.0, 0.0)
-            },
-            ImageConfig {
-                clear: ClearConfig::Color(0.0, 0.0, 0.0)
-            },
-        ]),
-        passes: Vec::from([
-            Pass {
-                shader: "examples/blur-pass/shaders/screen_shader.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([]),
-                output_resources: Vec::from([ 0 ]),
-            },
-            Pass {
-                shader: "examples/blur-pass/shaders/blur.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([ 0 ]),
-                output_resources: Vec::from([ 1 ]),
-            }
-        ])
+    let blur = |name: &str, direction: u32, input: &str, output: &str| {
+        Pass::fullscreen("examples/blur-pass/shaders/blur.comp", [input], [output])
+            .name(name)
+            .define("DIRECTION", direction)
+            .specialize(SpecConstant::new(0, "range", SpecValue::Int(2)))
     };
 
-    App::run(app_config, config, AudioConfig::None);
+    let config = DrawConfig::builder()
+        .images([ "scene", "horizontal", "blurred", "composite" ].map(ImageConfig::new))
+        .pass(Pass::new("examples/blur-pass/shaders/screen_shader.comp").name("scene").output("scene"))
+        .pass(blur("blur horizontal", 0, "scene", "horizontal"))
+        .pass(blur("blur vertical", 1, "horizontal", "blurred"))
+        .pass(Pass::fullscreen("examples/blur-pass/shaders/composite.comp", ["scene", "blurred"], ["composite"]).name("composite"))
+        .build();
+
+    App::run(app_config, config, AudioConfig::None)
 }
@@ -1,27 +1,16 @@
 use kiyo::app::app::{App, AppConfig};
 use kiyo::app::audio_orch::AudioConfig;
 use kiyo::app::draw_orch::{ClearConfig, DrawConfig, ImageConfig};
+use kiyo::Error;
 
-fn main() {
+fn main() -> Result<(), Error> {
 
-    let app_config = AppConfig {
-        width: 1000,
-        height: 1000,
-        vsync: true,
-        log_fps: false,
-        fullscreen: false,
-    };
+    let app_config = AppConfig::default();
 
     // Display a single image cleared to yellow
-    let config = DrawConfig {
-        images: Vec::from([
-            ImageConfig {
-                clear: ClearConfig::Color(1.0, 1.0, 0.0)
-            },
-        ]),
-        passes: Vec::from([
-        ])
-    };
+    let config = DrawConfig::builder()
+        .image(ImageConfig::new("output").clear(ClearConfig::Color(1.0, 1.0, 0.0)))
+        .build();
 
-    App::run(app_config, config, AudioConfig::None);
+    App::run(app_config, config, AudioConfig::None)
 }
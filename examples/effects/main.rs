@@ -0,0 +1,31 @@
+use kiyo::app::app::{App, AppConfig};
+use kiyo::app::audio_orch::AudioConfig;
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
+use kiyo::app::effects;
+use kiyo::app::effects::Tonemapper;
+use kiyo::app::image::ImageFormat;
+use kiyo::Error;
+
+const LUT_SIZE: u32 = 16;
+
+fn main() -> Result<(), Error> {
+
+    let app_config = AppConfig::default();
+
+    // The effects ping-pong between the scene and output images, the last image is displayed. The scene is HDR,
+    // bloom and tonemapping need float images to keep the values above 1.
+    let config = DrawConfig::builder()
+        .image_format(ImageFormat::Rgba16Float)
+        .images([ "scene", "bright", "scratch", "lut", "output", "final" ].map(ImageConfig::new))
+        .pass(Pass::new("examples/effects/shaders/scene.comp").name("scene").output("scene"))
+        .pass(Pass::new("examples/effects/shaders/warm_lut.comp").name("lut").output("lut").define("LUT_SIZE", LUT_SIZE))
+        .passes(effects::bloom("bloom", "scene", "bright", "scratch", "output", 0.6, 12.0, 0.8))
+        .pass(effects::tonemap("tonemap", "output", "scene", Tonemapper::Aces, 1.5))
+        .pass(effects::color_grade("grade", "scene", "lut", "output", LUT_SIZE, 1.0))
+        .pass(effects::fxaa("fxaa", "output", "scene"))
+        .pass(effects::chromatic_aberration("aberration", "scene", "output", 3.0))
+        .pass(effects::film_grain("grain", "output", "final", 0.06))
+        .build();
+
+    App::run(app_config, config, AudioConfig::None)
+}
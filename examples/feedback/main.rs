@@ -1,48 +1,37 @@
 use kiyo::app::App;
 use kiyo::app::app::AppConfig;
 use kiyo::app::audio_orch::AudioConfig;
-use kiyo::app::draw_orch::{ClearConfig, DispatchConfig, DrawConfig, ImageConfig, Pass};
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
+use kiyo::app::input::{Action, InputState, KeyBindings};
+use kiyo::Error;
 
-fn main() {
+/**
+ *  R restarts the simulation, S freezes it by turning the sharpen pass off or on.
+ */
+struct Bindings;
 
-    let app_config = AppConfig {
-        width: 1000,
-        height: 1000,
-        vsync: true,
-        log_fps: false,
-        fullscreen: false,
-    };
+impl KeyBindings for Bindings {
+    fn key_pressed(&mut self, key: u8, _input: &InputState) -> Vec<Action> {
+        match key {
+            b'R' => Vec::from([ Action::Reset ]),
+            b'S' => Vec::from([ Action::TogglePass("sharpen".to_string()) ]),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+
+    let app_config = AppConfig::default()
+        .key_bindings(Bindings);
 
-    let config = DrawConfig {
-        images: Vec::from([
-            ImageConfig {
-                clear: ClearConfig::None
-            },
-            ImageConfig {
-                clear: ClearConfig::None
-            },
-        ]),
-        passes: Vec::from([
-            Pass {
-                shader: "examples/feedback/shaders/setup.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([]),
-                output_resources: Vec::from([ 0 ]),
-            },
-            Pass {
-                shader: "examples/feedback/shaders/blur.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([ 0 ]),
-                output_resources: Vec::from([ 1 ]),
-            },
-            Pass {
-                shader: "examples/feedback/shaders/sharpen.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([ 1 ]),
-                output_resources: Vec::from([ 0 ]),
-            },
-        ])
-    };
+    let config = DrawConfig::builder()
+        .image(ImageConfig::persistent("state"))
+        .image(ImageConfig::persistent("blurred"))
+        .pass(Pass::new("examples/feedback/shaders/setup.comp").name("setup").output("state"))
+        .pass(Pass::fullscreen("examples/feedback/shaders/blur.comp", ["state"], ["blurred"]).name("blur"))
+        .pass(Pass::fullscreen("examples/feedback/shaders/sharpen.comp", ["blurred"], ["state"]).name("sharpen"))
+        .build();
 
-    App::run(app_config, config, AudioConfig::None);
+    App::run(app_config, config, AudioConfig::None)
 }
@@ -0,0 +1,65 @@
+use kiyo::app::app::{App, AppConfig};
+use kiyo::app::audio_orch::AudioConfig;
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
+use kiyo::app::hook::{FrameContext, FrameHook};
+use kiyo::Error;
+
+const SIZE: f32 = 1000.0;
+const GRAVITY: f32 = 1500.0;
+
+/**
+ *  Balls bouncing in the window, simulated in Rust and drawn by the shader. Clicking adds a ball, pausing the clock
+ *  pauses the simulation.
+ */
+struct Balls {
+    /// x, y, velocity x, velocity y and radius of each ball
+    balls: Vec<[f32; 5]>,
+}
+
+impl FrameHook for Balls {
+    fn update(&mut self, context: &mut FrameContext) {
+        let input = context.input();
+        let mouse = input.mouse();
+        if mouse[3] > 0.0 {
+            self.balls.push([mouse[0], mouse[1], 300.0, 0.0, 20.0 + 10.0 * (self.balls.len() % 4) as f32]);
+        }
+
+        let dt = context.delta().clamp(0.0, 0.05);
+        for [x, y, vx, vy, r] in &mut self.balls {
+            *vy += GRAVITY * dt;
+            *x += *vx * dt;
+            *y += *vy * dt;
+            if *x < *r || *x > SIZE - *r {
+                *vx = -*vx;
+                *x = x.clamp(*r, SIZE - *r);
+            }
+            if *y > SIZE - *r {
+                *vy = -*vy * 0.9;
+                *y = SIZE - *r;
+            }
+        }
+
+        let mut data = Vec::from([ self.balls.len() as f32 ]);
+        data.extend(self.balls.iter().flat_map(|[x, y, _, _, r]| [*x, *y, *r]));
+        context.upload(&data);
+    }
+}
+
+fn main() -> Result<(), Error> {
+
+
+    let config = DrawConfig::builder()
+        .image(ImageConfig::new("output"))
+        .pass(Pass::new("examples/frame-hook/shaders/balls.comp").name("balls").output("output"))
+        .build();
+
+    let balls = Balls {
+        balls: Vec::from([ [500.0, 200.0, 250.0, 0.0, 40.0] ]),
+    };
+    let app_config = AppConfig::default()
+        .width(SIZE as u32)
+        .height(SIZE as u32)
+        .frame_hook(balls);
+
+    App::run(app_config, config, AudioConfig::None)
+}
@@ -0,0 +1,53 @@
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+use kiyo::app::app::{App, AppConfig};
+use kiyo::app::audio_orch::AudioConfig;
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
+use kiyo::app::pipeline::{SpecConstant, SpecValue};
+use kiyo::Error;
+
+/*
+ * The scene of the blur-pass example, with or without its blur passes
+ */
+fn config(blur: bool) -> DrawConfig {
+    let blur_pass = |name: &str, direction: u32, input: &str, output: &str| {
+        Pass::fullscreen("examples/blur-pass/shaders/blur.comp", [input], [output])
+            .name(name)
+            .define("DIRECTION", direction)
+            .specialize(SpecConstant::new(0, "range", SpecValue::Int(4)))
+    };
+
+    let mut builder = DrawConfig::builder()
+        .image(ImageConfig::new("scene"))
+        .pass(Pass::new("examples/blur-pass/shaders/screen_shader.comp").name("scene").output("scene"));
+    if blur {
+        builder = builder
+            .images([ ImageConfig::new("horizontal"), ImageConfig::new("blurred") ])
+            .pass(blur_pass("blur horizontal", 0, "scene", "horizontal"))
+            .pass(blur_pass("blur vertical", 1, "horizontal", "blurred"));
+    }
+    builder
+        .image(ImageConfig::new("composite"))
+        .pass(Pass::fullscreen("examples/blur-pass/shaders/composite.comp", ["scene", if blur { "blurred" } else { "scene" }], ["composite"]).name("composite"))
+        .build()
+}
+
+fn main() -> Result<(), Error> {
+
+    // Toggle the blur every few seconds, the scene and composite images are kept
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for blur in [false, true].into_iter().cycle() {
+            thread::sleep(Duration::from_secs(4));
+            if sender.send(config(blur)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let app_config = AppConfig::default()
+        .draw_config_updates(receiver);
+
+    App::run(app_config, config(true), AudioConfig::None)
+}
@@ -1,32 +1,16 @@
 use kiyo::app::app::{App, AppConfig};
-use kiyo::app::draw_orch::{ClearConfig, DispatchConfig, DrawConfig, ImageConfig, Pass};
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
 use kiyo::app::audio_orch::{AudioConfig};
+use kiyo::Error;
 
-fn main() {
+fn main() -> Result<(), Error> {
 
-    let app_config = AppConfig {
-        width: 1000,
-        height: 1000,
-        vsync: true,
-        log_fps: false,
-        fullscreen: false,
-    };
+    let app_config = AppConfig::default();
 
-    let config = DrawConfig {
-        images: Vec::from([
-            ImageConfig {
-                clear: ClearConfig::Color(0.0, 0.0, 0.0)
-            },
-        ]),
-        passes: Vec::from([
-            Pass {
-                shader: "examples/simple-render/shaders/colors.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([]),
-                output_resources: Vec::from([ 0 ]),
-            },
-        ])
-    };
+    let config = DrawConfig::builder()
+        .image(ImageConfig::new("output"))
+        .pass(Pass::new("examples/simple-render/shaders/colors.comp").name("colors").output("output"))
+        .build();
 
-    App::run(app_config, config, AudioConfig::None);
+    App::run(app_config, config, AudioConfig::None)
 }
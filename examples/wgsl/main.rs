@@ -0,0 +1,16 @@
+use kiyo::app::app::{App, AppConfig};
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
+use kiyo::app::audio_orch::{AudioConfig};
+use kiyo::Error;
+
+fn main() -> Result<(), Error> {
+
+    let app_config = AppConfig::default();
+
+    let config = DrawConfig::builder()
+        .image(ImageConfig::new("output"))
+        .pass(Pass::new("examples/wgsl/shaders/colors.wgsl").name("colors").output("output"))
+        .build();
+
+    App::run(app_config, config, AudioConfig::None)
+}
@@ -0,0 +1,36 @@
+// Kiyo data
+// - WORKGROUP_SIZE, NUM_IMAGES and the other numeric macros are provided as constants by the engine
+
+struct PushConstants {
+    time: f32,
+    in_image: i32,
+    out_image: i32,
+    offset: vec2<f32>,
+    resolution: vec2<f32>,
+};
+
+var<push_constant> constants: PushConstants;
+@group( 0 ) @binding( 0 ) var images: binding_array<texture_storage_2d<rgba8unorm, read_write>, NUM_IMAGES>;
+
+// User data
+
+fn palette( t: f32, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>, d: vec3<f32> ) -> vec3<f32>
+{
+    return a + b * cos( 6.28318 * ( c * t + d ) );
+}
+
+@compute @workgroup_size( WORKGROUP_SIZE, WORKGROUP_SIZE, 1 )
+fn main( @builtin( global_invocation_id ) id: vec3<u32> )
+{
+    let p = vec2<i32>( id.xy );
+    if( p.x >= i32( constants.resolution.x ) || p.y >= i32( constants.resolution.y ) )
+    {
+        return;
+    }
+
+    let pos = ( vec2<f32>( p ) + constants.offset ) / constants.resolution - 0.5;
+
+    let t = constants.time * 0.1 + 0.5 * length( pos );
+    let color = palette( t, vec3( 0.5, 0.5, 0.5 ), vec3( 0.5, 0.5, 0.5 ), vec3( 1.0, 1.0, 1.0 ), vec3( 0.0, 0.33, 0.67 ) );
+    textureStore( images[ constants.out_image ], p, vec4<f32>( color, 1.0 ) );
+}
@@ -1,33 +1,17 @@
 use kiyo::app::app::{App, AppConfig};
-use kiyo::app::draw_orch::{ClearConfig, DispatchConfig, DrawConfig, ImageConfig, Pass};
+use kiyo::app::draw_orch::{DrawConfig, ImageConfig, Pass};
 use kiyo::app::audio_orch::{AudioConfig};
+use kiyo::Error;
 
-fn main() {
+fn main() -> Result<(), Error> {
     const USE_FILE:bool = true;
 
-    let app_config = AppConfig {
-        width: 1000,
-        height: 1000,
-        fullscreen: false,
-        vsync: true,
-        log_fps: false,
-    };
+    let app_config = AppConfig::default();
 
-    let config = DrawConfig {
-        images: Vec::from([
-            ImageConfig {
-                clear: ClearConfig::Color(0.0, 0.0, 0.0)
-            },
-        ]),
-        passes: Vec::from([
-            Pass {
-                shader: "examples/with-audio/shaders/colors.comp".to_string(),
-                dispatches: DispatchConfig::FullScreen,
-                input_resources: Vec::from([]),
-                output_resources: Vec::from([ 0 ]),
-            },
-        ])
-    };
+    let config = DrawConfig::builder()
+        .image(ImageConfig::new("output"))
+        .pass(Pass::new("examples/with-audio/shaders/colors.comp").name("colors").output("output"))
+        .build();
 
     // t: time in seconds
     fn audio_shader(t:f32) -> (f32, f32) {
@@ -39,8 +23,11 @@ fn main() {
 
         (a, b)
     }
-    App::run(app_config, config, if USE_FILE 
-        {AudioConfig::AudioFile("examples/with-audio/sample.wav".to_string())} else 
-        {AudioConfig::Program(audio_shader)}
-    );
+    let audio_config = if USE_FILE {
+        AudioConfig::AudioFile("examples/with-audio/sample.wav".to_string())
+    } else {
+        AudioConfig::Program(audio_shader)
+    };
+
+    App::run(app_config, config, audio_config)
 }
@@ -1,58 +1,119 @@
 use std::sync::{Arc, Mutex};
-use crate::app::StreamFactory;
+use std::sync::mpsc::Receiver;
 use crate::app::draw_orch::{DrawConfig};
 use crate::app::audio_orch::{AudioConfig};
-use crate::app::audio_orch::AudioConfig::{AudioFile, Program, None};
-use cpal::Stream;
-use cpal::traits::StreamTrait;
+use crate::app::hook::FrameHook;
+use crate::app::input::KeyBindings;
 use crate::app::{DrawOrchestrator};
+use crate::Error;
 
 pub struct App {
     pub cen: cen::app::App,
 }
 
-struct AudioPlayer {
-    stream: Stream,
+#[non_exhaustive]
+pub struct AppConfig {
+    pub width: u32,
+    pub height: u32,
+    pub vsync: bool,
+    pub log_fps: bool,
+    pub fullscreen: bool,
+    /// Dump all images of the first frame to files starting with this prefix
+    pub dump_images: Option<String>,
+    /// New DrawConfigs to switch to while running, unchanged images keep their contents
+    pub draw_config_updates: Option<Receiver<DrawConfig>>,
+    /// Custom key bindings, e.g. to reset a simulation or toggle passes
+    pub key_bindings: Option<Box<dyn KeyBindings>>,
+    /// Custom logic run before every frame, e.g. to drive a simulation from Rust
+    pub frame_hook: Option<Box<dyn FrameHook>>,
+    /// Name of the output device to play audio on, the default device when None. See `output_device_names`.
+    pub audio_device: Option<String>,
+    /// Control the clock with Space, Left, Right and Home. Turn off when shaders or the frame hook use these keys.
+    pub transport_keys: bool,
 }
 
-impl AudioPlayer {
-    fn new(func: fn(f32)->(f32, f32)) -> Self {
-        let sf = StreamFactory::default_factory().unwrap();
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            width: 1000,
+            height: 1000,
+            vsync: true,
+            log_fps: false,
+            fullscreen: false,
+            dump_images: None,
+            draw_config_updates: None,
+            key_bindings: None,
+            frame_hook: None,
+            audio_device: None,
+            transport_keys: true,
+        }
+    }
+}
 
-        let sample_rate = sf.config().sample_rate.0;
-        let mut sample_clock = 0;
-        let routin = Box::new(move |len: usize| -> Vec<f32> {
-            (0..len / 2) // len is apparently left *and* right
-                .flat_map(|_| {
-                    sample_clock = (sample_clock + 1) % sample_rate;
+impl AppConfig {
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
 
-                    let (l, r) = func(sample_clock as f32 / sample_rate as f32);
-                    vec![l, r]
-                })
-                .collect()
-        });
+    pub fn height(mut self, height: u32) -> Self {
+        self.height = height;
+        self
+    }
 
-        Self {
-            stream: sf.create_stream(routin).unwrap() // creates stream from function "routin"
-        }
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
     }
 
-    fn play(&self) {
-        StreamTrait::play(&self.stream).unwrap();
+    pub fn log_fps(mut self, log_fps: bool) -> Self {
+        self.log_fps = log_fps;
+        self
     }
-}
 
-pub struct AppConfig {
-    pub width: u32,
-    pub height: u32,
-    pub vsync: bool,
-    pub log_fps: bool,
-    pub fullscreen: bool,
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn dump_images(mut self, prefix: &str) -> Self {
+        self.dump_images = Some(prefix.to_string());
+        self
+    }
+
+    pub fn draw_config_updates(mut self, updates: Receiver<DrawConfig>) -> Self {
+        self.draw_config_updates = Some(updates);
+        self
+    }
+
+    pub fn key_bindings(mut self, key_bindings: impl KeyBindings + 'static) -> Self {
+        self.key_bindings = Some(Box::new(key_bindings));
+        self
+    }
+
+    pub fn frame_hook(mut self, frame_hook: impl FrameHook + 'static) -> Self {
+        self.frame_hook = Some(Box::new(frame_hook));
+        self
+    }
+
+    pub fn audio_device(mut self, name: &str) -> Self {
+        self.audio_device = Some(name.to_string());
+        self
+    }
+
+    pub fn transport_keys(mut self, transport_keys: bool) -> Self {
+        self.transport_keys = transport_keys;
+        self
+    }
 }
 
 impl App {
 
-    pub fn run(app_config: AppConfig, draw_config: DrawConfig, audio_config: AudioConfig) {
+    /*
+     * Validate the configs and run the application until its window is closed. Fails when the configs are invalid or
+     * the GPU resources can't be created. Audio device failures don't stop the app, it will run silently instead.
+     */
+    pub fn run(app_config: AppConfig, draw_config: DrawConfig, audio_config: AudioConfig) -> Result<(), Error> {
 
         let cen_conf = cen::app::app::AppConfig::default()
             .width(app_config.width)
@@ -62,20 +123,35 @@ impl App {
             .log_fps(app_config.log_fps);
 
         // Parse orchestrator
-        let orchestrator = DrawOrchestrator::new(draw_config, audio_config.clone());
-
-        // audio program (not synced to render time like audio file?)
-        let player:Option<AudioPlayer> = match audio_config {
-            Program(program) => Some(AudioPlayer::new(program)),
-            AudioFile(_) => Option::None,
-            None => Option::None
-        };
-        if let Some(p) = &player {
-            p.play();
+        let mut orchestrator = DrawOrchestrator::new(draw_config, audio_config)?;
+        if let Some(prefix) = app_config.dump_images {
+            orchestrator.dump_images(&prefix);
+        }
+        if let Some(updates) = app_config.draw_config_updates {
+            orchestrator.receive_configs(updates);
+        }
+        if let Some(key_bindings) = app_config.key_bindings {
+            orchestrator.set_key_bindings(key_bindings);
         }
+        if let Some(frame_hook) = app_config.frame_hook {
+            orchestrator.set_frame_hook(frame_hook);
+        }
+        if let Some(device) = app_config.audio_device {
+            orchestrator.set_audio_device(device);
+        }
+        orchestrator.set_transport_keys(app_config.transport_keys);
 
         // Run graphics backend
+        // cen drives the orchestrator from the main thread only
+        #[allow(clippy::arc_with_non_send_sync)]
         let orch = Arc::new(Mutex::new(orchestrator));
-        cen::app::App::run(cen_conf, orch.clone(), Some(orch));
+        cen::app::App::run(cen_conf, orch.clone(), Some(orch.clone()));
+
+        // Errors during setup, like failing to allocate the images
+        let failure = orch.lock().unwrap().take_failure();
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,220 @@
+use std::time::{Duration, Instant};
+
+/**
+ *  Playback clock driving the shader time and audio.
+ *  Supports pausing, seeking, looping a region, changing speed and stepping frame by frame.
+ */
+pub struct Clock {
+    time: f32,
+    playing: bool,
+    speed: f32,
+    loop_region: Option<(f32, f32)>,
+    frame_duration: f32,
+    last_update: Instant,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Self {
+            time: 0.0,
+            playing: true,
+            speed: 1.0,
+            loop_region: None,
+            frame_duration: 1.0 / 60.0,
+            last_update: Instant::now(),
+        }
+    }
+
+    /*
+     * Advance the clock by the wall time passed since the previous update
+     */
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update);
+        self.last_update = now;
+
+        self.elapse(elapsed.as_secs_f32());
+    }
+
+    /*
+     * Let @seconds of wall time pass, scaled by the speed while playing
+     */
+    fn elapse(&mut self, seconds: f32) {
+        if self.playing {
+            self.advance(seconds * self.speed);
+        }
+    }
+
+    /*
+     * Move the clock by @delta seconds, wrapping around the loop region if one is set
+     */
+    pub fn advance(&mut self, delta: f32) {
+        self.time = self.wrap(self.time + delta);
+    }
+
+    fn wrap(&self, time: f32) -> f32 {
+        match self.loop_region {
+            Some((start, end)) if end > start && (time < start || time >= end) => {
+                start + (time - start).rem_euclid(end - start)
+            },
+            _ => time.max(0.0),
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.time)
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = self.wrap(time);
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn loop_region(&self) -> Option<(f32, f32)> {
+        self.loop_region
+    }
+
+    pub fn set_loop_region(&mut self, loop_region: Option<(f32, f32)>) {
+        self.loop_region = loop_region;
+        self.time = self.wrap(self.time);
+    }
+
+    pub fn frame_duration(&self) -> f32 {
+        self.frame_duration
+    }
+
+    pub fn set_frame_rate(&mut self, frame_rate: f32) {
+        self.frame_duration = 1.0 / frame_rate.max(1.0);
+    }
+
+    /*
+     * Pause and move @frames frames forward, or backward when negative
+     */
+    pub fn step(&mut self, frames: i32) {
+        self.playing = false;
+        self.advance(frames as f32 * self.frame_duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_time(clock: &Clock, time: f32) {
+        assert!((clock.time() - time).abs() < 1e-5, "time is {}, expected {}", clock.time(), time);
+    }
+
+    #[test]
+    fn speed_scales_elapsed_time() {
+        let mut clock = Clock::new();
+        clock.set_speed(2.0);
+        clock.elapse(0.5);
+        assert_time(&clock, 1.0);
+        clock.set_speed(0.25);
+        clock.elapse(2.0);
+        assert_time(&clock, 1.5);
+    }
+
+    #[test]
+    fn paused_clock_stands_still() {
+        let mut clock = Clock::new();
+        clock.pause();
+        clock.elapse(1.0);
+        assert_time(&clock, 0.0);
+        clock.toggle();
+        assert!(clock.is_playing());
+    }
+
+    #[test]
+    fn negative_speed_is_clamped() {
+        let mut clock = Clock::new();
+        clock.set_speed(-1.0);
+        assert_eq!(clock.speed(), 0.0);
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut clock = Clock::new();
+        clock.seek(3.5);
+        clock.set_loop_region(Some((1.0, 3.0)));
+        // Setting the region moves the time into it
+        assert_time(&clock, 1.5);
+        clock.seek(1.0);
+        clock.advance(2.5);
+        assert_time(&clock, 1.5);
+        clock.advance(-1.0);
+        assert_time(&clock, 2.5);
+        clock.seek(7.25);
+        assert_time(&clock, 1.25);
+    }
+
+    #[test]
+    fn empty_loop_region_is_ignored() {
+        let mut clock = Clock::new();
+        clock.set_loop_region(Some((2.0, 2.0)));
+        clock.seek(5.0);
+        assert_time(&clock, 5.0);
+    }
+
+    #[test]
+    fn negative_time_is_clamped() {
+        let mut clock = Clock::new();
+        clock.seek(-2.0);
+        assert_time(&clock, 0.0);
+        clock.seek(0.01);
+        clock.step(-3);
+        assert_time(&clock, 0.0);
+    }
+
+    #[test]
+    fn stepping_pauses_and_moves_by_frames() {
+        let mut clock = Clock::new();
+        clock.set_frame_rate(25.0);
+        clock.step(5);
+        assert!(!clock.is_playing());
+        assert_time(&clock, 0.2);
+        clock.step(-2);
+        assert_time(&clock, 0.12);
+    }
+
+    #[test]
+    fn frame_rate_is_at_least_one() {
+        let mut clock = Clock::new();
+        assert_eq!(clock.frame_duration(), 1.0 / 60.0);
+        clock.set_frame_rate(0.0);
+        assert_eq!(clock.frame_duration(), 1.0);
+    }
+}
@@ -5,6 +5,7 @@ https://github.com/ytanimura/sound-shader/tree/main/src
 
 use cpal::traits::{DeviceTrait, HostTrait};
 use cpal::{Device, Sample, SampleFormat, Stream, StreamConfig, SupportedStreamConfig};
+use crate::Error;
 
 pub struct StreamFactory {
 	device: Device,
@@ -20,27 +21,34 @@ impl StreamFactory {
 		Self { device, config }
 	}
 
-	pub fn default_factory() -> Result<StreamFactory, String> {
+	pub fn default_factory() -> Result<StreamFactory, Error> {
 		let host = cpal::default_host();
 		let device = host
 			.default_output_device()
-			.ok_or("failed to find output device")?;
+			.ok_or(Error::AudioDevice("failed to find output device".to_string()))?;
+		Self::from_device(device)
+	}
+
+	/// Create a factory for the output device with the given name, see [`output_device_names`]
+	pub fn named_factory(name: &str) -> Result<StreamFactory, Error> {
+		let devices = cpal::default_host()
+			.output_devices()
+			.map_err(|e| Error::AudioDevice(format!("{:?}", e)))?;
+		let device = find_output_device(devices, name, |d| d.name().ok())?;
+		Self::from_device(device)
+	}
+
+	fn from_device(device: Device) -> Result<StreamFactory, Error> {
 		let config = device
 			.default_output_config()
-			.map_err(|e| format!("{:?}", e))?;
-		if config.channels() != 2 {
-			return Err(format!(
-				"audio channel must be 2\nchannels: {}",
-				config.channels()
-			));
-		}
+			.map_err(|e| Error::AudioDevice(format!("{:?}", e)))?;
 		Ok(Self { device, config })
 	}
 
 	pub fn create_stream(
 		&self,
 		routin: impl FnMut(usize) -> Vec<f32> + Send + 'static,
-	) -> Result<Stream, String> {
+	) -> Result<Stream, Error> {
 		match self.config.sample_format() {
 			SampleFormat::F32 => self.sub_get_stream::<f32, _>(routin),
 			SampleFormat::I16 => self.sub_get_stream::<i16, _>(routin),
@@ -50,19 +58,68 @@ impl StreamFactory {
 	fn sub_get_stream<T: Sample, F: FnMut(usize) -> Vec<f32> + Send + 'static>(
 		&self,
 		mut routin: F,
-	) -> Result<Stream, String> {
+	) -> Result<Stream, Error> {
+		let channels = self.config.channels() as usize;
 		self.device
 			.build_output_stream(
 				&self.config.clone().into(),
 				move |output: &mut [T], _: &cpal::OutputCallbackInfo| {
-					routin(output.len())
-						.into_iter()
-						.zip(output)
-						.for_each(|(b, a)| *a = cpal::Sample::from(&b))
+					// The routine renders stereo, mix it to the device's channel layout
+					let mut frame = vec![0.0; channels];
+					routin(output.len() / channels * 2)
+						.chunks_exact(2)
+						.zip(output.chunks_exact_mut(channels))
+						.for_each(|(b, a)| {
+							mix_stereo(b[0], b[1], &mut frame);
+							a.iter_mut().zip(&frame).for_each(|(a, b)| *a = cpal::Sample::from(b));
+						})
 				},
 				|err| eprintln!("an error occurred on stream: {:?}", err),
 			)
-			.map_err(|e| format!("{:?}", e))
+			.map_err(|e| Error::AudioDevice(format!("{:?}", e)))
+	}
+}
+
+/// Names of all output devices of the default host
+pub fn output_device_names() -> Vec<String> {
+	cpal::default_host()
+		.output_devices()
+		.map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+		.unwrap_or_default()
+}
+
+/// The device named @name out of @devices, @device_name gives the name of a device.
+/// Generic over the device type, as rodio brings its own version of cpal.
+pub fn find_output_device<D>(
+	devices: impl Iterator<Item = D>,
+	name: &str,
+	device_name: impl Fn(&D) -> Option<String>,
+) -> Result<D, Error> {
+	let mut devices = devices;
+	devices
+		.find(|d| device_name(d).as_deref() == Some(name))
+		.ok_or_else(|| Error::AudioDevice(format!(
+			"failed to find output device \"{}\"\navailable devices: {:?}",
+			name,
+			output_device_names()
+		)))
+}
+
+/// Up- or downmix a stereo frame to the channel count of @output.
+/// Layouts follow the common wave channel orders: mono, stereo, quad, 5.1 and 7.1.
+/// Other channel counts alternate left and right.
+pub fn mix_stereo(l: f32, r: f32, output: &mut [f32]) {
+	let c = (l + r) * 0.5;
+	match output.len() {
+		0 => {}
+		1 => output[0] = c,
+		4 => output.copy_from_slice(&[l, r, l, r]),
+		6 => output.copy_from_slice(&[l, r, c, 0.0, l, r]),
+		8 => output.copy_from_slice(&[l, r, c, 0.0, l, r, l, r]),
+		_ => output
+			.iter_mut()
+			.enumerate()
+			.for_each(|(i, o)| *o = if i % 2 == 0 { l } else { r }),
 	}
 }
 
@@ -87,3 +144,58 @@ impl StreamFactory {
 // 	stream.play().unwrap();
 // 	std::thread::sleep(std::time::Duration::from_millis(1000));
 // }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mix(l: f32, r: f32, channels: usize) -> Vec<f32> {
+		let mut output = vec![-1.0; channels];
+		mix_stereo(l, r, &mut output);
+		output
+	}
+
+	#[test]
+	fn mono_averages() {
+		assert_eq!(mix(1.0, 0.5, 1), vec![0.75]);
+	}
+
+	#[test]
+	fn stereo_is_unchanged() {
+		assert_eq!(mix(1.0, 0.5, 2), vec![1.0, 0.5]);
+	}
+
+	#[test]
+	fn quad_repeats_front_to_back() {
+		assert_eq!(mix(1.0, 0.5, 4), vec![1.0, 0.5, 1.0, 0.5]);
+	}
+
+	#[test]
+	fn surround_has_a_center_and_silent_lfe() {
+		assert_eq!(mix(1.0, 0.5, 6), vec![1.0, 0.5, 0.75, 0.0, 1.0, 0.5]);
+		assert_eq!(mix(1.0, 0.5, 8), vec![1.0, 0.5, 0.75, 0.0, 1.0, 0.5, 1.0, 0.5]);
+	}
+
+	#[test]
+	fn other_layouts_alternate() {
+		assert_eq!(mix(1.0, 0.5, 3), vec![1.0, 0.5, 1.0]);
+		assert_eq!(mix(1.0, 0.5, 5), vec![1.0, 0.5, 1.0, 0.5, 1.0]);
+		assert_eq!(mix(1.0, 0.5, 0), Vec::<f32>::new());
+	}
+
+	#[test]
+	fn devices_are_found_by_name() {
+		let devices = ["Speakers", "HDMI", "Headphones"];
+		let found = find_output_device(devices.into_iter(), "HDMI", |d| Some(d.to_string()));
+		assert_eq!(found.unwrap(), "HDMI");
+	}
+
+	#[test]
+	fn unnamed_and_missing_devices_are_skipped() {
+		let devices = [None, Some("Speakers")];
+		let found = find_output_device(devices.into_iter(), "Speakers", |d| d.map(str::to_string));
+		assert_eq!(found.unwrap(), Some("Speakers"));
+		let missing = find_output_device(devices.into_iter(), "HDMI", |d| d.map(str::to_string));
+		assert!(matches!(missing, Err(Error::AudioDevice(message)) if message.contains("\"HDMI\"")));
+	}
+}
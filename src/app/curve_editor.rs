@@ -0,0 +1,204 @@
+use egui::{pos2, vec2, Color32, ComboBox, Context, DragValue, Pos2, Rect, Sense, Shape, Stroke, Ui};
+use log::{error, info};
+use crate::app::clock::Clock;
+use crate::app::keyframes::{Animation, Interpolation, Keyframe, Track};
+
+/**
+ *  Egui window to edit the keyframes of an animation.
+ */
+pub struct CurveEditor {
+    pub open: bool,
+    track: usize,
+    selected: Option<usize>,
+    value_range: (f32, f32),
+    dragging: bool,
+    path: String,
+}
+
+impl Default for CurveEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CurveEditor {
+    pub fn new() -> CurveEditor {
+        Self {
+            open: false,
+            track: 0,
+            selected: None,
+            value_range: (0.0, 1.0),
+            dragging: false,
+            path: "animation.txt".to_string(),
+        }
+    }
+
+    pub fn show(&mut self, context: &Context, animation: &mut Animation, clock: &mut Clock, length: f32) {
+        let mut open = self.open;
+        egui::Window::new("Curve editor")
+            .open(&mut open)
+            .default_size([640.0, 320.0])
+            .show(context, |ui| {
+                self.file_controls(ui, animation);
+                if animation.tracks.is_empty() {
+                    ui.label("No tracks, add them to the animation of the DrawConfig");
+                    return;
+                }
+                self.track = self.track.min(animation.tracks.len() - 1);
+                self.track_controls(ui, animation, clock);
+                self.plot(ui, &mut animation.tracks[self.track], clock, length);
+            });
+        self.open = open;
+    }
+
+    fn file_controls(&mut self, ui: &mut Ui, animation: &mut Animation) {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.path).desired_width(200.0));
+            if ui.button("Save").clicked() {
+                match animation.save(&self.path) {
+                    Ok(()) => info!("Saved animation to {}", self.path),
+                    Err(e) => error!("Failed to save animation: {}", e),
+                }
+            }
+            if ui.button("Load").clicked() {
+                match Animation::load(&self.path) {
+                    // Keep the track layout, shaders refer to the tracks by index
+                    Ok(loaded) => for track in animation.tracks.iter_mut() {
+                        if let Some(t) = loaded.get(&track.name) {
+                            track.keyframes = t.keyframes.clone();
+                        }
+                    },
+                    Err(e) => error!("Failed to load animation: {}", e),
+                }
+                self.selected = None;
+            }
+        });
+    }
+
+    fn track_controls(&mut self, ui: &mut Ui, animation: &mut Animation, clock: &Clock) {
+        ui.horizontal(|ui| {
+            ComboBox::from_label("Track")
+                .selected_text(animation.tracks[self.track].name.as_str())
+                .show_ui(ui, |ui| {
+                    for (i, t) in animation.tracks.iter().enumerate() {
+                        if ui.selectable_value(&mut self.track, i, t.name.as_str()).changed() {
+                            self.selected = None;
+                        }
+                    }
+                });
+
+            let track = &mut animation.tracks[self.track];
+            if ui.button("Add key").clicked() {
+                let time = clock.time();
+                self.selected = Some(track.insert(Keyframe {
+                    time,
+                    value: track.evaluate(time),
+                    interpolation: Interpolation::Linear,
+                }));
+            }
+            if let Some(i) = self.selected.filter(|&i| i < track.keyframes.len()) {
+                if ui.button("Remove key").clicked() {
+                    track.keyframes.remove(i);
+                    self.selected = None;
+                }
+            }
+        });
+
+        let track = &mut animation.tracks[self.track];
+        if let Some(i) = self.selected.filter(|&i| i < track.keyframes.len()) {
+            let mut moved = false;
+            ui.horizontal(|ui| {
+                let key = &mut track.keyframes[i];
+                moved = ui.add(DragValue::new(&mut key.time).speed(0.01).range(0.0..=f32::MAX).prefix("time: ")).changed();
+                ui.add(DragValue::new(&mut key.value).speed(0.01).prefix("value: "));
+                ComboBox::from_id_salt("interpolation")
+                    .selected_text(key.interpolation.name())
+                    .show_ui(ui, |ui| {
+                        for i in [Interpolation::Step, Interpolation::Linear, Interpolation::Smoothstep, Interpolation::Bezier(0.42, 0.0, 0.58, 1.0)] {
+                            if ui.selectable_label(key.interpolation.name() == i.name(), i.name()).clicked() {
+                                key.interpolation = i;
+                            }
+                        }
+                    });
+                if let Interpolation::Bezier(x1, y1, x2, y2) = &mut key.interpolation {
+                    ui.add(DragValue::new(x1).speed(0.01).range(0.0..=1.0));
+                    ui.add(DragValue::new(y1).speed(0.01));
+                    ui.add(DragValue::new(x2).speed(0.01).range(0.0..=1.0));
+                    ui.add(DragValue::new(y2).speed(0.01));
+                }
+            });
+            if moved {
+                self.selected = Some(track.sort(i));
+            }
+        }
+    }
+
+    fn plot(&mut self, ui: &mut Ui, track: &mut Track, clock: &mut Clock, length: f32) {
+        let (response, painter) = ui.allocate_painter(ui.available_size().max(vec2(100.0, 100.0)), Sense::click());
+        let rect = response.rect.shrink(8.0);
+
+        // Only rescale while nothing is being dragged, otherwise the key runs away from the cursor
+        if !self.dragging {
+            let (min, max) = track.keyframes.iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), k| (min.min(k.value), max.max(k.value)));
+            self.value_range = if min > max { (0.0, 1.0) } else if min == max { (min - 1.0, max + 1.0) } else {
+                let padding = (max - min) * 0.1;
+                (min - padding, max + padding)
+            };
+        }
+        let (low, high) = self.value_range;
+        let length = length.max(track.keyframes.last().map(|k| k.time).unwrap_or(0.0)).max(f32::EPSILON);
+        let to_screen = |time: f32, value: f32| pos2(
+            rect.left() + time / length * rect.width(),
+            rect.bottom() - (value - low) / (high - low) * rect.height(),
+        );
+        let from_screen = |pos: Pos2| (
+            ((pos.x - rect.left()) / rect.width() * length).max(0.0),
+            low + (rect.bottom() - pos.y) / rect.height() * (high - low),
+        );
+
+        painter.rect_filled(response.rect, 2.0, ui.visuals().extreme_bg_color);
+
+        // Curve
+        let points = (0..=rect.width() as usize)
+            .map(|x| {
+                let time = x as f32 / rect.width() * length;
+                to_screen(time, track.evaluate(time))
+            })
+            .collect::<Vec<Pos2>>();
+        painter.add(Shape::line(points, Stroke::new(1.5, ui.visuals().selection.bg_fill)));
+
+        // Playhead
+        let head = to_screen(clock.time(), low).x;
+        painter.line_segment([pos2(head, rect.top()), pos2(head, rect.bottom())], Stroke::new(1.0, Color32::RED));
+
+        // Keyframes
+        let mut dragged = None;
+        for (i, key) in track.keyframes.iter_mut().enumerate() {
+            let center = to_screen(key.time, key.value);
+            let handle = ui.interact(Rect::from_center_size(center, vec2(12.0, 12.0)), response.id.with(i), Sense::click_and_drag());
+            if handle.clicked() || handle.drag_started() {
+                self.selected = Some(i);
+            }
+            if handle.dragged() {
+                if let Some(pos) = handle.interact_pointer_pos() {
+                    (key.time, key.value) = from_screen(pos);
+                    dragged = Some(i);
+                }
+            }
+            let color = if self.selected == Some(i) { Color32::WHITE } else { ui.visuals().text_color() };
+            painter.circle_filled(center, if handle.hovered() { 6.0 } else { 4.0 }, color);
+        }
+        self.dragging = dragged.is_some();
+        if let Some(i) = dragged {
+            self.selected = Some(track.sort(i));
+        }
+
+        // Scrub by clicking the background
+        if response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                clock.seek(from_screen(pos).0);
+            }
+        }
+    }
+}
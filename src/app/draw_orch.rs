@@ -1,31 +1,55 @@
-use std::collections::HashMap;
-use std::mem::size_of;
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::mem::{offset_of, size_of};
 use ash::vk;
 use ash::vk::{BufferImageCopy, BufferUsageFlags, DeviceSize, Extent3D, ImageAspectFlags, ImageLayout, ImageSubresourceLayers, ImageUsageFlags, Offset3D};
 use bytemuck::{Pod, Zeroable};
-use cen::graphics::pipeline_store::{PipelineConfig, PipelineKey};
 use cen::graphics::Renderer;
 use cen::graphics::renderer::RenderComponent;
-use cen::vulkan::{Buffer, CommandBuffer, DescriptorSetLayout, Image, PipelineErr};
+use cen::vulkan::{Buffer, CommandBuffer};
 use glam::{UVec3};
-use log::{error, info};
-use crate::app::audio_orch::{AudioConfig};
-use crate::app::audio_orch::AudioConfig::AudioFile;
-use std::fs::File;
-use std::io::BufReader;
-use rodio::{Decoder, OutputStream, Sink};
+use log::{error, info, warn};
+use crate::app::audio_orch::{export_audio, open_audio_file, open_output_stream, AudioConfig, AudioPlayer};
+use crate::app::audio_orch::AudioConfig::{AudioFile, Program};
+use std::path::{Path, PathBuf};
+use rodio::{OutputStream, Sink, Source};
 use core::time::{Duration};
-use std::ops::Add;
-use std::process::exit;
-use std::thread;
+use std::sync::{mpsc, Arc};
+use std::sync::mpsc::Receiver;
+use std::{fs, thread};
 use cen::app::gui::GuiComponent;
-use egui::{menu, Context, TopBottomPanel};
-use egui::epaint::ColorMode::UV;
+use egui::{menu, Color32, Context, RichText, TopBottomPanel};
 use gpu_allocator::MemoryLocation;
-use crate::app::png::{write_png_image};
+use crate::app::export::{downsample, write_image, DownsampleFilter, ExportFormat, RowWriter};
+use crate::app::clock::Clock;
+use crate::app::curve_editor::CurveEditor;
+use crate::app::effects;
+use crate::app::hook::{FrameContext, FrameHook};
+use crate::app::image::{bind_images, ImageFormat, StorageImage};
+use crate::app::input;
+use crate::app::input::{read_egui, Action, InputState, KeyBindings, INPUT_SIZE, KEY_HOME, KEY_LEFT, KEY_RIGHT, KEY_SPACE};
+use crate::app::inspector::{probe_region, Inspector, Probe, ProbeImage, PROBE_SIZE};
+use crate::app::keyframes::{macro_name, Animation};
+use crate::app::pipeline::{canonical, compile_shader, ComputePipeline, PushDescriptorLayout, ShaderLanguage, ShaderWatcher, SpecConstant, SpecValue};
+use crate::app::profiler::{PassSample, Profiler, TimestampPool};
+use crate::app::readback::{decode_pixels, PixelFormat, Tonemap};
+use crate::app::reconfigure::ConfigDiff;
+use crate::app::reflect::{check_interface, FieldType, PushConstantField, ShaderInterface};
+use crate::app::shader_editor::ShaderEditor;
+use crate::app::shader_errors;
+use crate::app::shader_errors::ShaderError;
+use crate::app::visualize::{VisualizePass, VisualizeSettings, Visualizer};
+use crate::Error;
+
+/// Maximum amount of input and output images of a single pass, available in shaders as MAX_PASS_IMAGES
+pub const MAX_PASS_IMAGES: usize = 8;
+
+const WORKGROUP_SIZE: u32 = 32;
 
 #[derive(Copy)]
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum DispatchConfig
 {
     Count( u32, u32, u32 ),
@@ -38,48 +62,517 @@ pub struct PushConstants {
     pub time: f32,
     pub in_image: i32,
     pub out_image: i32,
+    pub _padding: i32,
+    /// Position of the rendered region in the final image, nonzero when exporting in tiles
+    pub offset: [f32; 2],
+    /// Size of the final image in pixels
+    pub resolution: [f32; 2],
+    pub in_count: i32,
+    pub out_count: i32,
+    /// All input images of the pass, `in_image` is the first of them
+    pub in_images: [i32; MAX_PASS_IMAGES],
+    /// All output images of the pass, `out_image` is the first of them
+    pub out_images: [i32; MAX_PASS_IMAGES],
+}
+
+/*
+ * Layout of PushConstants as shaders may declare it, shaders can use any prefix of it
+ */
+const PUSH_CONSTANT_FIELDS: [PushConstantField; 10] = [
+    PushConstantField { name: "time", offset: offset_of!(PushConstants, time) as u32, ty: FieldType::Float },
+    PushConstantField { name: "in_image", offset: offset_of!(PushConstants, in_image) as u32, ty: FieldType::Int },
+    PushConstantField { name: "out_image", offset: offset_of!(PushConstants, out_image) as u32, ty: FieldType::Int },
+    PushConstantField { name: "_padding", offset: offset_of!(PushConstants, _padding) as u32, ty: FieldType::Int },
+    PushConstantField { name: "offset", offset: offset_of!(PushConstants, offset) as u32, ty: FieldType::Vec2 },
+    PushConstantField { name: "resolution", offset: offset_of!(PushConstants, resolution) as u32, ty: FieldType::Vec2 },
+    PushConstantField { name: "in_count", offset: offset_of!(PushConstants, in_count) as u32, ty: FieldType::Int },
+    PushConstantField { name: "out_count", offset: offset_of!(PushConstants, out_count) as u32, ty: FieldType::Int },
+    PushConstantField { name: "in_images", offset: offset_of!(PushConstants, in_images) as u32, ty: FieldType::IntArray(MAX_PASS_IMAGES as u32) },
+    PushConstantField { name: "out_images", offset: offset_of!(PushConstants, out_images) as u32, ty: FieldType::IntArray(MAX_PASS_IMAGES as u32) },
+];
+
+/**
+ *  Reference to an image of the DrawConfig, by its position in `images` or by its name.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub enum ImageRef {
+    Index(u32),
+    Name(String),
+}
+
+impl From<u32> for ImageRef {
+    fn from(index: u32) -> Self {
+        ImageRef::Index(index)
+    }
+}
+
+impl From<&str> for ImageRef {
+    fn from(name: &str) -> Self {
+        ImageRef::Name(name.to_string())
+    }
+}
+
+impl From<String> for ImageRef {
+    fn from(name: String) -> Self {
+        ImageRef::Name(name)
+    }
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageRef::Index(index) => write!(f, "{}", index),
+            ImageRef::Name(name) => write!(f, "\"{}\"", name),
+        }
+    }
 }
 
+#[non_exhaustive]
 pub struct Pass {
+    /// Used in logs and errors instead of the pass index
+    pub name: Option<String>,
     pub shader: String,
     pub dispatches: DispatchConfig,
-    pub input_resources: Vec<u32>,
-    pub output_resources: Vec<u32>,
+    pub input_resources: Vec<ImageRef>,
+    pub output_resources: Vec<ImageRef>,
+    /// Macros for this pass only, so one shader file can be used by several passes with different constants
+    pub defines: HashMap<String, String>,
+    /// Specialization constants, these can be tweaked from the GUI without recompiling the shader
+    pub specialization: Vec<SpecConstant>,
+}
+
+impl Pass {
+    /*
+     * A full screen pass running the compute shader at @shader, without images
+     */
+    pub fn new(shader: &str) -> Pass {
+        Self {
+            name: None,
+            shader: shader.to_string(),
+            dispatches: DispatchConfig::FullScreen,
+            input_resources: Vec::new(),
+            output_resources: Vec::new(),
+            defines: HashMap::new(),
+            specialization: Vec::new(),
+        }
+    }
+
+    /*
+     * A full screen pass running @shader, reading @inputs and writing @outputs
+     */
+    pub fn fullscreen<I, O>(shader: &str, inputs: I, outputs: O) -> Pass
+    where
+        I: IntoIterator,
+        I::Item: Into<ImageRef>,
+        O: IntoIterator,
+        O::Item: Into<ImageRef>,
+    {
+        Self::new(shader).inputs(inputs).outputs(outputs)
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn dispatches(mut self, dispatches: DispatchConfig) -> Self {
+        self.dispatches = dispatches;
+        self
+    }
+
+    /*
+     * Add @image to the images the pass reads
+     */
+    pub fn input(mut self, image: impl Into<ImageRef>) -> Self {
+        self.input_resources.push(image.into());
+        self
+    }
+
+    pub fn inputs<I>(mut self, images: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<ImageRef>,
+    {
+        self.input_resources.extend(images.into_iter().map(Into::into));
+        self
+    }
+
+    /*
+     * Add @image to the images the pass writes
+     */
+    pub fn output(mut self, image: impl Into<ImageRef>) -> Self {
+        self.output_resources.push(image.into());
+        self
+    }
+
+    pub fn outputs<O>(mut self, images: O) -> Self
+    where
+        O: IntoIterator,
+        O::Item: Into<ImageRef>,
+    {
+        self.output_resources.extend(images.into_iter().map(Into::into));
+        self
+    }
+
+    /*
+     * Define the macro @name as @value for this pass. WGSL shaders get it as a constant, so for them @value has to be
+     * a number or a boolean.
+     */
+    pub fn define(mut self, name: &str, value: impl ToString) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn specialize(mut self, constant: SpecConstant) -> Self {
+        self.specialization.push(constant);
+        self
+    }
 }
 
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum ClearConfig {
     None,
     Color(f32,f32,f32),
 }
 
 #[derive(Clone)]
+#[non_exhaustive]
 pub struct ImageConfig {
+    /// Lets passes refer to the image by name, shaders get its index as `IMG_<NAME>`
+    pub name: Option<String>,
     pub clear: ClearConfig,
 }
 
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            clear: ClearConfig::Color(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl ImageConfig {
+    /*
+     * An image named @name, cleared to black every frame
+     */
+    pub fn new(name: &str) -> ImageConfig {
+        Self {
+            name: Some(name.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /*
+     * An image named @name that keeps its contents between frames, for feedback effects
+     */
+    pub fn persistent(name: &str) -> ImageConfig {
+        Self::new(name).clear(ClearConfig::None)
+    }
+
+    pub fn clear(mut self, clear: ClearConfig) -> Self {
+        self.clear = clear;
+        self
+    }
+}
+
+#[derive(Default)]
+#[non_exhaustive]
 pub struct DrawConfig {
     pub passes: Vec<Pass>,
     pub images: Vec<ImageConfig>,
+    /// Keyframed parameters, evaluated every frame and readable in shaders as `params[PARAM_<NAME>]`
+    pub animation: Animation,
+    /// Format of all images, GLSL shaders get its layout qualifier as IMAGE_FORMAT
+    pub image_format: ImageFormat,
+}
+
+/**
+ *  Builds a DrawConfig from images and passes in the order they're added.
+ *
+ *  Every config type has a constructor or Default and chained setters, so configs never have to be written as struct
+ *  literals and new fields don't break them:
+ *  ```
+ *  use kiyo::app::app::AppConfig;
+ *  use kiyo::app::audio_orch::AudioConfig;
+ *  use kiyo::app::draw_orch::{ClearConfig, DispatchConfig, DrawConfig, ImageConfig, Pass};
+ *  use kiyo::app::image::ImageFormat;
+ *  use kiyo::app::keyframes::{Animation, Interpolation, Track};
+ *  use kiyo::app::pipeline::{SpecConstant, SpecValue};
+ *
+ *  let app_config = AppConfig::default()
+ *      .width(640)
+ *      .height(480)
+ *      .vsync(false);
+ *
+ *  let animation = Animation::default()
+ *      .track(Track::new("brightness")
+ *          .keyframe(0.0, 0.0, Interpolation::Linear)
+ *          .keyframe(2.0, 1.0, Interpolation::Step));
+ *
+ *  let draw_config = DrawConfig::builder()
+ *      .image_format(ImageFormat::Rgba16Float)
+ *      .image(ImageConfig::new("scene"))
+ *      .image(ImageConfig::new("output").clear(ClearConfig::None))
+ *      .pass(Pass::new("shaders/scene.comp").name("scene").output("scene"))
+ *      .pass(Pass::fullscreen("shaders/blur.comp", ["scene"], ["output"])
+ *          .dispatches(DispatchConfig::FullScreen)
+ *          .define("DIRECTION", 0)
+ *          .specialize(SpecConstant::new(0, "range", SpecValue::Int(2))))
+ *      .animation(animation)
+ *      .build();
+ *
+ *  assert_eq!(app_config.width, 640);
+ *  assert_eq!(draw_config.passes.len(), 2);
+ *  assert_eq!(draw_config.animation.get("brightness").unwrap().evaluate(1.0), 0.5);
+ *  assert!(matches!(AudioConfig::default(), AudioConfig::None));
+ *  ```
+ */
+#[derive(Default)]
+pub struct DrawConfigBuilder {
+    config: DrawConfig,
+}
+
+impl DrawConfigBuilder {
+    pub fn image(mut self, image: ImageConfig) -> Self {
+        self.config.images.push(image);
+        self
+    }
+
+    pub fn images(mut self, images: impl IntoIterator<Item = ImageConfig>) -> Self {
+        self.config.images.extend(images);
+        self
+    }
+
+    pub fn pass(mut self, pass: Pass) -> Self {
+        self.config.passes.push(pass);
+        self
+    }
+
+    pub fn passes(mut self, passes: impl IntoIterator<Item = Pass>) -> Self {
+        self.config.passes.extend(passes);
+        self
+    }
+
+    pub fn animation(mut self, animation: Animation) -> Self {
+        self.config.animation = animation;
+        self
+    }
+
+    pub fn image_format(mut self, format: ImageFormat) -> Self {
+        self.config.image_format = format;
+        self
+    }
+
+    /*
+     * The config, validated when the app starts or switches to it
+     */
+    pub fn build(self) -> DrawConfig {
+        self.config
+    }
+}
+
+impl DrawConfig {
+    pub fn builder() -> DrawConfigBuilder {
+        DrawConfigBuilder::default()
+    }
+
+    /*
+     * Index in `images` of the image @image refers to
+     */
+    pub fn image_index(&self, image: &ImageRef) -> Result<u32, Error> {
+        match image {
+            ImageRef::Index(index) if (*index as usize) < self.images.len() => Ok(*index),
+            ImageRef::Index(index) => Err(Error::Config(format!("Image {} doesn't exist, only {} images are provided", index, self.images.len()))),
+            ImageRef::Name(name) => self.images.iter()
+                .position(|i| i.name.as_ref() == Some(name))
+                .map(|i| i as u32)
+                .ok_or(Error::Config(format!("No image is named \"{}\"", name))),
+        }
+    }
+
+    /*
+     * Name of pass @index for logs, its index if it has none
+     */
+    pub fn pass_name(&self, index: usize) -> String {
+        match &self.passes[index].name {
+            Some(name) => name.clone(),
+            None => index.to_string(),
+        }
+    }
+
+    /*
+     * Name of image @index for the GUI, its index if it has none
+     */
+    pub fn image_name(&self, index: usize) -> String {
+        match &self.images[index].name {
+            Some(name) => name.clone(),
+            None => index.to_string(),
+        }
+    }
+
+    /*
+     * Macros provided by kiyo to all shaders, running in workgroups of @workgroup_size
+     */
+    pub fn macros(&self, workgroup_size: u32) -> HashMap<String, String> {
+        self.macro_list(workgroup_size).into_iter().collect()
+    }
+
+    /*
+     * The shader macros in the order they're defined, a name occurring twice would override the first
+     */
+    fn macro_list(&self, workgroup_size: u32) -> Vec<(String, String)> {
+        let mut macros = Vec::from([
+            ("NUM_IMAGES".to_string(), self.images.len().to_string()),
+            ("WORKGROUP_SIZE".to_string(), workgroup_size.to_string()),
+            ("MAX_PASS_IMAGES".to_string(), MAX_PASS_IMAGES.to_string()),
+            ("IMAGE_FORMAT".to_string(), self.image_format.glsl().to_string()),
+        ]);
+        macros.extend(self.animation.macros());
+        macros.extend(input::macros(self.animation.tracks.len()));
+        macros.push(("USER_DATA".to_string(), (self.animation.tracks.len() + INPUT_SIZE).to_string()));
+        macros.extend(self.image_macros());
+        macros
+    }
+
+    /*
+     * Shader macros mapping each image name to its index, e.g. IMG_SCENE
+     */
+    pub fn image_macros(&self) -> Vec<(String, String)> {
+        self.images.iter().enumerate()
+            .filter_map(|(i, c)| c.name.as_ref().map(|name| (format!("IMG_{}", macro_name(name)), i.to_string())))
+            .collect()
+    }
+}
+
+/**
+ *  A shader compiled with a set of macros, shared by all passes using the same file and defines.
+ */
+pub struct CompiledShader {
+    pub path: String,
+    canonical_path: PathBuf,
+    macros: HashMap<String, String>,
+    /// Empty until the shader compiled successfully once
+    code: Vec<u32>,
+    /// Error of the last compilation, the last working code stays in use
+    error: Option<ShaderError>,
+}
+
+impl CompiledShader {
+    fn new(path: &str, macros: HashMap<String, String>, images: (u32, ImageFormat)) -> CompiledShader {
+        let mut shader = CompiledShader {
+            path: path.to_string(),
+            canonical_path: canonical(Path::new(path)),
+            macros,
+            code: Vec::new(),
+            error: None,
+        };
+        shader.compile(images);
+        shader
+    }
+
+    /*
+     * Compile the shader again for the amount and format of @images, returns whether its code changed
+     */
+    fn compile(&mut self, images: (u32, ImageFormat)) -> bool {
+        match Self::compile_checked(Path::new(&self.path), &self.macros, images) {
+            Ok(code) => {
+                self.code = code;
+                self.error = None;
+                true
+            },
+            Err(e) => {
+                error!("{}", e);
+                self.error = Some(ShaderError::new(&self.path, &e));
+                false
+            },
+        }
+    }
+
+    /*
+     * Compile the shader at @path and check that its bindings and push constants match what kiyo provides
+     * for @images, given as their amount and format
+     */
+    fn compile_checked(path: &Path, macros: &HashMap<String, String>, images: (u32, ImageFormat)) -> Result<Vec<u32>, Error> {
+        let code = compile_shader(path, macros)?;
+        let (image_count, image_format) = images;
+        let interface = ShaderInterface {
+            image_count,
+            image_format,
+            push_constants: &PUSH_CONSTANT_FIELDS,
+            push_constant_size: size_of::<PushConstants>() as u32,
+        };
+        match check_interface(&code, &interface) {
+            Err(Error::Shader(e)) => Err(Error::Shader(format!("{} doesn't match kiyo's interface:\n{}", path.display(), e))),
+            result => result.map(|_| code),
+        }
+    }
 }
 
 pub struct ShaderPass {
+    pub name: String,
     pub dispatches: DispatchConfig,
     pub in_images: Vec<u32>,
     pub out_images: Vec<u32>,
-    pub pipeline_handle: PipelineKey,
+    /// Index of the pass' shader in the compiled shaders
+    pub shader: usize,
+    pub specialization: Vec<SpecConstant>,
+    pub pipeline: Option<ComputePipeline>,
+    /// Set when the shader or specialization changed, the pipeline is rebuilt before the next frame
+    pub rebuild: bool,
+    /// Disabled passes are skipped
+    pub enabled: bool,
 }
 
 pub struct ImageResource {
-    pub image: Image,
+    pub name: Option<String>,
+    pub image: StorageImage,
     pub clear: ClearConfig,
 }
 
+/*
+ * Image receiving the output of a render, in @src_layout before and left in @dst_layout after
+ */
+struct RenderTarget<'a> {
+    image: &'a vk::Image,
+    src_layout: ImageLayout,
+    dst_layout: ImageLayout,
+}
+
+/*
+ * Region of the final image covered by a render at @time, a tile is rendered at @offset in an image of @resolution
+ */
+#[derive(Copy, Clone)]
+struct FrameView {
+    time: f32,
+    offset: [f32; 2],
+    resolution: [f32; 2],
+}
+
 struct ImgExport {
     width: u32,
     height: u32,
     filename: String,
     do_export: bool,
+    format: ExportFormat,
+    supersampling: u32,
+    filter: DownsampleFilter,
+    alpha: bool,
+    tonemap: Tonemap,
+    use_current_time: bool,
+    time: f32,
+    tiled: bool,
+    tile_size: u32,
+    tile_overlap: u32,
+    /// Frames exported from the start time on, numbered in the filename when there's more than one
+    frames: u32,
+    fps: f32,
+    /// Write the audio of the exported frames to `<filename>.wav`
+    audio: bool,
+    sample_rate: u32,
+    /// Start time and next frame of the running export
+    sequence: Option<(f32, u32)>,
 }
 
 /**
@@ -88,23 +581,66 @@ struct ImgExport {
 pub struct DrawOrchestrator {
     draw_config: DrawConfig,
     audio_config: AudioConfig,
+    /// Output device to play audio on, None for the default device
+    audio_device: Option<String>,
     audio_stream: Option<OutputStream>,
     sink: Option<Sink>,
-    pub compute_descriptor_set_layout: Option<DescriptorSetLayout>,
+    audio_player: Option<AudioPlayer>,
+    pub compute_descriptor_set_layout: Option<Arc<PushDescriptorLayout>>,
     pub image_resources: Option<Vec<ImageResource>>,
     pub passes: Option<Vec<ShaderPass>>,
+    parameter_buffers: Option<Vec<Buffer>>,
     image_export: ImgExport,
+    dump_request: Option<String>,
+    frame_count: u64,
+    shaders: Vec<CompiledShader>,
+    shader_watcher: Option<ShaderWatcher>,
+    /// Error that stopped the setup, nothing is rendered after it
+    failure: Option<Error>,
+    /// Whether Space, Left, Right and Home control the clock
+    transport_keys: bool,
+    clock: Clock,
+    timeline_length: f32,
+    curve_editor: CurveEditor,
+    shader_editor: ShaderEditor,
+    profiler: Profiler,
+    inspector: Inspector,
+    visualizer: Visualizer,
+    /// Renders the visualization of the shown image, created once an image is visualized
+    visualize_pass: Option<VisualizePass>,
+    /// Nanoseconds per GPU timestamp tick and valid timestamp bits of the queue, None when the device can't time
+    /// compute passes
+    timestamps: Option<(f32, u32)>,
+    /// Timestamp queries of each frame in flight
+    timestamp_pools: Option<Vec<Option<TimestampPool>>>,
+    /// New configs to switch to while running
+    config_updates: Option<Receiver<DrawConfig>>,
+    /// Shaders saved in the editor, recompiled before the next frame
+    saved_shaders: Vec<PathBuf>,
+    input: InputState,
+    key_bindings: Option<Box<dyn KeyBindings>>,
+    frame_hook: Option<Box<dyn FrameHook>>,
+    /// Data uploaded by the frame hook
+    user_data: Vec<f32>,
+    /// Clock time the frame hook last ran at
+    hook_time: f32,
+    /// Clear all images in the next frame, also those without a clear config
+    reset: bool,
     workgroup_size: u32
 }
 
 impl DrawOrchestrator {
-    pub fn new(draw_config: DrawConfig, audio_config: AudioConfig) -> DrawOrchestrator {
-        Self {
-            workgroup_size: 32,
+    pub fn new(draw_config: DrawConfig, audio_config: AudioConfig) -> Result<DrawOrchestrator, Error> {
+        Self::validate(&draw_config, &audio_config)?;
+
+        Ok(Self {
+            workgroup_size: WORKGROUP_SIZE,
             draw_config,
             audio_config,
+            audio_device: None,
             audio_stream: None,
             sink: None,
+            audio_player: None,
             compute_descriptor_set_layout: None,
             image_resources: None,
             passes: None,
@@ -113,118 +649,702 @@ impl DrawOrchestrator {
                 filename: "output".to_string(),
                 width: 1920,
                 height: 1080,
+                format: ExportFormat::Png8,
+                supersampling: 1,
+                filter: DownsampleFilter::Box,
+                alpha: true,
+                tonemap: Tonemap::Clamp,
+                use_current_time: true,
+                time: 0.0,
+                tiled: false,
+                tile_size: 2048,
+                tile_overlap: 32,
+                frames: 1,
+                fps: 60.0,
+                audio: false,
+                sample_rate: 48000,
+                sequence: None,
             },
+            clock: Clock::new(),
+            timeline_length: 60.0,
+            parameter_buffers: None,
+            curve_editor: CurveEditor::new(),
+            shader_editor: ShaderEditor::new(),
+            profiler: Profiler::new(),
+            inspector: Inspector::new(),
+            visualizer: Visualizer::new(),
+            visualize_pass: None,
+            timestamps: None,
+            timestamp_pools: None,
+            config_updates: None,
+            saved_shaders: Vec::new(),
+            input: InputState::new(),
+            key_bindings: None,
+            frame_hook: None,
+            user_data: Vec::new(),
+            hook_time: 0.0,
+            reset: false,
+            dump_request: None,
+            frame_count: 0,
+            shaders: Vec::new(),
+            shader_watcher: None,
+            failure: None,
+            transport_keys: true,
+        })
+    }
+
+    /*
+     * Check the configs for errors that would otherwise only show up during rendering
+     */
+    fn validate(draw_config: &DrawConfig, audio_config: &AudioConfig) -> Result<(), Error> {
+        let macros = draw_config.macros(WORKGROUP_SIZE);
+        for (i, image) in draw_config.images.iter().enumerate() {
+            if let Some(name) = &image.name {
+                if draw_config.images[..i].iter().any(|other| other.name.as_ref() == Some(name)) {
+                    return Err(Error::Config(format!("Multiple images are named \"{}\"", name)));
+                }
+            }
+        }
+
+        // Names become shader macros, distinct names can still map to the same one
+        let images = draw_config.images.iter().filter_map(|c| c.name.as_deref()).collect::<Vec<&str>>();
+        let parameters = draw_config.animation.tracks.iter().map(|t| t.name.as_str()).collect::<Vec<&str>>();
+        for (kind, prefix, names) in [("Images", "IMG", images), ("Parameters", "PARAM", parameters)] {
+            for (i, name) in names.iter().enumerate() {
+                if let Some(other) = names[..i].iter().find(|other| macro_name(other) == macro_name(name)) {
+                    return Err(Error::Config(format!("{} \"{}\" and \"{}\" both map to the shader macro {}_{}", kind, other, name, prefix, macro_name(name))));
+                }
+            }
+        }
+        let macro_list = draw_config.macro_list(WORKGROUP_SIZE);
+        for (i, (name, _)) in macro_list.iter().enumerate() {
+            if macro_list[..i].iter().any(|(other, _)| other == name) {
+                return Err(Error::Config(format!("The shader macro {} is defined twice, rename the image or parameter it's made from", name)));
+            }
+        }
+
+        for (i, pass) in draw_config.passes.iter().enumerate() {
+            if let Some(name) = &pass.name {
+                if draw_config.passes[..i].iter().any(|other| other.name.as_ref() == Some(name)) {
+                    return Err(Error::Config(format!("Multiple passes are named \"{}\"", name)));
+                }
+            }
+            if pass.input_resources.len() > MAX_PASS_IMAGES || pass.output_resources.len() > MAX_PASS_IMAGES {
+                return Err(Error::Config(format!("Pass {} uses more than {} input or output images", draw_config.pass_name(i), MAX_PASS_IMAGES)));
+            }
+            for image in pass.input_resources.iter().chain(pass.output_resources.iter()) {
+                draw_config.image_index(image)
+                    .map_err(|e| Error::Config(format!("Pass {}: {}", draw_config.pass_name(i), e)))?;
+            }
+            if let Some(name) = pass.defines.keys().find(|name| macros.contains_key(*name)) {
+                return Err(Error::Config(format!("Pass {} defines {}, which is provided by kiyo", draw_config.pass_name(i), name)));
+            }
+            ShaderLanguage::from_path(Path::new(&pass.shader))?;
+            if effects::is_builtin(&pass.shader) {
+                if effects::builtin_source(&pass.shader).is_none() {
+                    return Err(Error::Shader(format!("No built-in shader is named {}", pass.shader)));
+                }
+                if effects::needs_float_images(&pass.shader) && !draw_config.image_format.is_float() {
+                    return Err(Error::Config(format!("Pass {} needs a float image format, the images are {:?}", draw_config.pass_name(i), draw_config.image_format)));
+                }
+            } else if !Path::new(&pass.shader).is_file() {
+                return Err(Error::Shader(format!("Shader file not found: {}", pass.shader)));
+            }
+        }
+
+        if let AudioFile(file) = audio_config {
+            open_audio_file(file)?;
         }
+
+        Ok(())
     }
 
-    fn export(&mut self, renderer: &mut Renderer, width: u32, height: u32) {
+    /*
+     * Start audio playback on the configured output device
+     */
+    fn start_audio(&mut self) -> Result<(), Error> {
+        match self.audio_config.clone() {
+            Program(program) => {
+                let player = AudioPlayer::new(program, self.audio_device.as_deref())?;
+                player.play()?;
+                self.audio_player = Some(player);
+            },
+            AudioFile(file) => {
+                let (stream, stream_handle) = open_output_stream(self.audio_device.as_deref())?;
+                let sink = Sink::try_new(&stream_handle)?;
+                let source = open_audio_file(&file)?;
+                if let Some(duration) = source.total_duration() {
+                    self.timeline_length = duration.as_secs_f32();
+                }
 
-        info!("Exporting...");
-        let output_image = Image::new(
-            &renderer.device,
-            &mut renderer.allocator,
-            width,
-            height,
-            ImageUsageFlags::STORAGE | ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::TRANSFER_SRC
-        );
-        let mut buffer = Buffer::new(
-            &renderer.device,
-            &mut renderer.allocator,
-            MemoryLocation::GpuToCpu,
-            (size_of::<u8>() as u32 * 4 * width * height) as DeviceSize,
-            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::TRANSFER_DST
-        );
-        let image_resources = Self::create_image_resources(renderer, &self.draw_config, width, height);
+                sink.append(source);
+                sink.play();
+                self.audio_stream = Some(stream);
+                self.sink = Some(sink);
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Start exporting the frames of the export settings, one per app frame so they don't all hold GPU memory at once.
+     * The audio of the frames is written right away.
+     */
+    fn start_export(&mut self) {
+        let settings = &self.image_export;
+        if settings.format.needs_float_images() && !self.draw_config.image_format.is_float() {
+            error!("Failed to export image: {} needs a float image format, the images are {:?}", settings.format.name(), self.draw_config.image_format);
+            return;
+        }
+        let start = if settings.use_current_time { self.clock.time() } else { settings.time };
+        if settings.audio {
+            self.export_audio(start, settings.frames as f32 / settings.fps);
+        }
+        self.image_export.sequence = Some((start, 0));
+    }
+
+    /*
+     * Export the next frame of the running export
+     */
+    fn export_next_frame(&mut self, renderer: &mut Renderer) {
+        let Some((start, frame)) = self.image_export.sequence.take() else {
+            return;
+        };
+        let settings = &self.image_export;
+        let time = start + frame as f32 / settings.fps;
+        let output_file = match settings.frames {
+            1 => format!("{}.{}", settings.filename, settings.format.extension()),
+            _ => format!("{}_{:05}.{}", settings.filename, frame, settings.format.extension()),
+        };
+        self.export(renderer, time, output_file);
+        if frame + 1 < self.image_export.frames {
+            self.image_export.sequence = Some((start, frame + 1));
+        }
+    }
+
+    /*
+     * Render the frame at @time with the export settings and write it to @output_file
+     */
+    fn export(&mut self, renderer: &mut Renderer, time: f32, output_file: String) {
+
+        info!("Exporting {}...", output_file);
+        let settings = &self.image_export;
+        let (format, filter, alpha, tonemap, factor) = (settings.format, settings.filter, settings.alpha, self.export_tonemap(), settings.supersampling.max(1));
+        let (out_width, out_height) = (settings.width, settings.height);
+        let (width, height) = (out_width * factor, out_height * factor);
+        let view = FrameView {
+            time,
+            offset: [0.0, 0.0],
+            resolution: [width as f32, height as f32],
+        };
+
+        // Images past the device limit can only be rendered in parts
+        let max_dimension = unsafe {
+            renderer.instance.handle().get_physical_device_properties(renderer.physical_device).limits.max_image_dimension2_d
+        };
+        if settings.tiled || width > max_dimension || height > max_dimension {
+            let tile_size = settings.tile_size.clamp(factor, max_dimension / 2).div_ceil(factor) * factor;
+            let overlap = settings.tile_overlap.max(factor).min(max_dimension / 4).div_ceil(factor) * factor;
+            match RowWriter::new(out_width, out_height, format, alpha, tonemap, &output_file) {
+                Ok(writer) => self.export_tiled(renderer, view, writer, tile_size, overlap, factor, filter),
+                Err(e) => error!("Failed to export image: {}", e),
+            }
+            return;
+        }
+
+        let image_format = self.draw_config.image_format;
+        let resources = StorageImage::new(&renderer.device, &renderer.allocator, width, height, image_format, ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::TRANSFER_SRC)
+            .and_then(|output_image| Ok((output_image, Self::create_image_resources(renderer, &self.draw_config.images, image_format, width, height)?)));
+        let (output_image, image_resources) = match resources {
+            Ok(resources) => resources,
+            Err(e) => {
+                error!("Failed to export image: {}", e);
+                return;
+            }
+        };
+        let mut buffer = Self::create_readback_buffer(renderer, width, height, image_format);
+        // Exports don't depend on the mouse and keyboard, they see no input at all
+        let parameters = self.parameters(self.draw_config.animation.evaluate(time), &InputState::new());
+        let mut parameter_buffer = Self::create_parameter_buffer(renderer, parameters.len());
+        Self::write_parameters(&mut parameter_buffer, &parameters);
 
         let mut command_buffer = renderer.create_command_buffer();
         command_buffer.begin();
-        {
-            self.do_render(renderer, &mut command_buffer, &image_resources, &output_image.handle(), ImageLayout::UNDEFINED, ImageLayout::TRANSFER_SRC_OPTIMAL);
-
-            command_buffer.copy_image_to_buffer(
-                &output_image,
-                ImageLayout::TRANSFER_SRC_OPTIMAL,
-                &buffer,
-                &[
-                    BufferImageCopy::default()
-                        .buffer_image_height(output_image.height)
-                        .buffer_offset(0)
-                        .image_extent(Extent3D::default().width(output_image.width).height(output_image.height).depth(1))
-                        .image_offset(Offset3D::default())
-                        .image_subresource(ImageSubresourceLayers::default()
-                            .layer_count(1)
-                            .mip_level(0)
-                            .aspect_mask(ImageAspectFlags::COLOR)
-                            .base_array_layer(0)
-                        )
-                ]
-            );
-        }
+        self.record_readback(renderer, &mut command_buffer, &image_resources, &parameter_buffer, view, &output_image, &buffer);
         command_buffer.end();
 
-        let filename = self.image_export.filename.clone();
         renderer.submit_single_time_command_buffer(command_buffer, Box::new(move || {
             // TODO: This is to keep the image alive until submission, but that should happen automagically
             let image = output_image;
             image.handle();
             let _image_resources = image_resources;
+            let _parameter_buffer = parameter_buffer;
 
-            // Write png
+            // Write image
             thread::spawn(move || {
                 let memory = buffer.mapped();
-                let output_file = filename.clone().add(".png");
-                write_png_image(memory, width, height, output_file.as_str());
-                info!("Finished exporting png image to {}", output_file);
+                let result = PixelFormat::from_vk(image_format.vk())
+                    .and_then(|pixel_format| decode_pixels(memory, width, height, width as usize * pixel_format.bytes_per_pixel(), pixel_format))
+                    .and_then(|pixels| {
+                        let pixels = downsample(&pixels, width, height, factor, filter);
+                        write_image(pixels, out_width, out_height, format, alpha, tonemap, output_file.as_str())
+                    });
+                match result {
+                    Ok(()) => info!("Finished exporting image to {}", output_file),
+                    Err(e) => error!("Failed to export image: {}", e),
+                }
             });
         }));
     }
 
     /*
-     * Perform a compute writing to @target_image
+     * Render @view in square tiles of @tile_size pixels, each extended by @overlap pixels on all sides so passes
+     * sampling their neighbourhood see the same data as in a full render. Rows of tiles are stitched together,
+     * downsampled by @factor and handed to a thread streaming them into @writer.
      */
-    fn do_render(&self, renderer: &mut Renderer, command_buffer: &mut CommandBuffer, image_resources: &Vec<ImageResource>, target_image: &vk::Image, src_layout: ImageLayout, dst_layout: ImageLayout) {
+    #[allow(clippy::too_many_arguments)]
+    fn export_tiled(&mut self, renderer: &mut Renderer, view: FrameView, mut writer: RowWriter, tile_size: u32, overlap: u32, factor: u32, filter: DownsampleFilter) {
+        let (width, height) = (view.resolution[0] as u32, view.resolution[1] as u32);
+        let render_size = tile_size + 2 * overlap;
+        info!("Rendering {}x{} image in {} tiles", width, height, width.div_ceil(tile_size) * height.div_ceil(tile_size));
 
-        // Clear all images with a clear config
-        {
-            for i in image_resources {
-                renderer.transition_image(
-                    &command_buffer,
-                    &i.image.handle(),
-                    vk::ImageLayout::GENERAL,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    vk::PipelineStageFlags::TOP_OF_PIPE,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::AccessFlags::NONE,
-                    vk::AccessFlags::TRANSFER_WRITE
-                );
+        let image_format = self.draw_config.image_format;
+        let resources = PixelFormat::from_vk(image_format.vk())
+            .and_then(|pixel_format| Ok((
+                pixel_format,
+                StorageImage::new(&renderer.device, &renderer.allocator, render_size, render_size, image_format, ImageUsageFlags::TRANSFER_DST | ImageUsageFlags::TRANSFER_SRC)?,
+                Self::create_image_resources(renderer, &self.draw_config.images, image_format, render_size, render_size)?,
+            )));
+        let (pixel_format, output_image, image_resources) = match resources {
+            Ok(resources) => resources,
+            Err(e) => {
+                error!("Failed to export image: {}", e);
+                return;
+            }
+        };
+        let mut buffer = Self::create_readback_buffer(renderer, render_size, render_size, image_format);
+        let parameters = self.parameters(self.draw_config.animation.evaluate(view.time), &InputState::new());
+        let mut parameter_buffer = Self::create_parameter_buffer(renderer, parameters.len());
+        Self::write_parameters(&mut parameter_buffer, &parameters);
 
-                match &i.clear {
-                    ClearConfig::None => {},
-                    ClearConfig::Color(r, g, b) => {
-                        unsafe {
-                            renderer.device.handle()
-                                .cmd_clear_color_image(
-                                    command_buffer.handle(),
-                                    *i.image.handle(),
-                                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                                    &vk::ClearColorValue {
-                                        float32: [*r, *g, *b, 1f32]
-                                    },
-                                    &[vk::ImageSubresourceRange {
-                                        aspect_mask: ImageAspectFlags::COLOR,
-                                        base_mip_level: 0,
-                                        level_count: 1,
-                                        base_array_layer: 0,
-                                        layer_count: 1,
-                                    }]
-                                );
-                        }
+        let (sender, receiver) = mpsc::channel::<Vec<f32>>();
+        let writer_thread = thread::spawn(move || {
+            for rows in receiver {
+                writer.write_rows(&rows)?;
+            }
+            writer.finish()
+        });
+
+        for tile_y in (0..height).step_by(tile_size as usize) {
+            // Band of full width rows including the overlap, clamped to the image like a full render would be
+            let band_start = tile_y.saturating_sub(overlap);
+            let band_end = (tile_y + tile_size + overlap).min(height);
+            let mut band = vec![0.0f32; (width * (band_end - band_start) * 4) as usize];
+
+            for tile_x in (0..width).step_by(tile_size as usize) {
+                let origin = (tile_x as i64 - overlap as i64, tile_y as i64 - overlap as i64);
+                let tile_view = FrameView {
+                    offset: [origin.0 as f32, origin.1 as f32],
+                    ..view
+                };
+
+                let mut command_buffer = renderer.create_command_buffer();
+                command_buffer.begin();
+                self.record_readback(renderer, &mut command_buffer, &image_resources, &parameter_buffer, tile_view, &output_image, &buffer);
+                command_buffer.end();
+                renderer.submit_single_time_command_buffer(command_buffer, Box::new(|| {}));
+                if let Err(e) = unsafe { renderer.device.handle().device_wait_idle() } {
+                    error!("Failed to export image: {}", e);
+                    return;
+                }
+
+                // Copy the tile without its horizontal overlap into the band
+                let tile = match decode_pixels(buffer.mapped(), render_size, render_size, render_size as usize * pixel_format.bytes_per_pixel(), pixel_format) {
+                    Ok(tile) => tile,
+                    Err(e) => {
+                        error!("Failed to export image: {}", e);
+                        return;
+                    }
+                };
+                let columns = tile_x..(tile_x + tile_size).min(width);
+                for y in band_start..band_end {
+                    let src = (((y as i64 - origin.1) as u32 * render_size + overlap) * 4) as usize;
+                    let dst = ((y - band_start) * width + tile_x) as usize * 4;
+                    let count = columns.len() * 4;
+                    band[dst..dst + count].copy_from_slice(&tile[src..src + count]);
+                }
+            }
+
+            // Downsample the band and keep the rows belonging to this row of tiles
+            let band = downsample(&band, width, band_end - band_start, factor, filter);
+            let row_length = (width / factor * 4) as usize;
+            let first = ((tile_y - band_start) / factor) as usize;
+            let count = (tile_size.min(height - tile_y) / factor) as usize;
+            if sender.send(band[first * row_length..(first + count) * row_length].to_vec()).is_err() {
+                break;
+            }
+        }
+        drop(sender);
+
+        thread::spawn(move || {
+            match writer_thread.join() {
+                Ok(Ok(())) => info!("Finished exporting tiled image"),
+                Ok(Err(e)) => error!("Failed to export image: {}", e),
+                Err(_) => error!("Failed to export image: writer panicked"),
+            }
+        });
+    }
+
+    /*
+     * Curve mapping HDR values for integer exports, 8-bit images have no values above 1 to map
+     */
+    fn export_tonemap(&self) -> Tonemap {
+        if self.draw_config.image_format.is_float() {
+            self.image_export.tonemap
+        } else {
+            Tonemap::Clamp
+        }
+    }
+
+    /*
+     * Write every image and the parameters as of the next rendered frame to files starting with @prefix
+     */
+    pub fn dump_images(&mut self, prefix: &str) {
+        self.dump_request = Some(prefix.to_string());
+    }
+
+    pub fn set_key_bindings(&mut self, key_bindings: Box<dyn KeyBindings>) {
+        self.key_bindings = Some(key_bindings);
+    }
+
+    pub fn set_frame_hook(&mut self, frame_hook: Box<dyn FrameHook>) {
+        self.frame_hook = Some(frame_hook);
+    }
+
+    pub fn set_transport_keys(&mut self, transport_keys: bool) {
+        self.transport_keys = transport_keys;
+    }
+
+    pub fn set_audio_device(&mut self, name: String) {
+        self.audio_device = Some(name);
+    }
+
+    /*
+     * The error that stopped the setup, if any
+     */
+    pub fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    /*
+     * Perform @action of a key binding
+     */
+    fn perform(&mut self, action: Action) {
+        let mut set_pass = |name: &str, enabled: Option<bool>| {
+            match self.passes.iter_mut().flatten().find(|p| p.name == name) {
+                Some(pass) => pass.enabled = enabled.unwrap_or(!pass.enabled),
+                None => warn!("Key binding refers to pass \"{}\", which doesn't exist", name),
+            }
+        };
+        match action {
+            Action::Reset => {
+                self.reset = true;
+                self.clock.seek(0.0);
+            },
+            Action::TogglePass(name) => set_pass(&name, None),
+            Action::EnablePass(name, enabled) => set_pass(&name, Some(enabled)),
+            Action::TogglePlayback => self.clock.toggle(),
+            Action::Seek(time) => self.clock.seek(time),
+        }
+    }
+
+    /*
+     * Switch to the DrawConfigs sent over @updates while running, when several arrive within a frame only the
+     * last is used
+     */
+    pub fn receive_configs(&mut self, updates: Receiver<DrawConfig>) {
+        self.config_updates = Some(updates);
+    }
+
+    /*
+     * Record copies of @region, or all pixels, of the images of the previous frame into @buffers
+     */
+    fn record_image_copies(renderer: &Renderer, command_buffer: &mut CommandBuffer, image_resources: &[ImageResource], buffers: &[Buffer], region: Option<[u32; 4]>) {
+        for (resource, buffer) in image_resources.iter().zip(buffers) {
+            // Wait on the passes of the previous frame, submitted earlier on the same queue
+            renderer.transition_image(
+                command_buffer,
+                resource.image.handle(),
+                vk::ImageLayout::GENERAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::MEMORY_WRITE,
+                vk::AccessFlags::TRANSFER_READ
+            );
+            let region = region.unwrap_or([0, 0, resource.image.width, resource.image.height]);
+            Self::record_region_copy(renderer, command_buffer, &resource.image, buffer, region);
+            renderer.transition_image(
+                command_buffer,
+                resource.image.handle(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::AccessFlags::SHADER_WRITE
+            );
+        }
+    }
+
+    /*
+     * Read back the region around the pixel the inspector asks for from every image of the previous frame
+     */
+    fn probe(&mut self, renderer: &mut Renderer) {
+        let Some(image_resources) = &self.image_resources else {
+            return;
+        };
+        let (width, height) = (image_resources[0].image.width, image_resources[0].image.height);
+        let format = match PixelFormat::from_vk(image_resources[0].image.format.vk()) {
+            Ok(format) => format,
+            Err(e) => {
+                error!("Failed to inspect pixels: {}", e);
+                return;
+            }
+        };
+        let Some(pixel) = self.inspector.request(width, height) else {
+            return;
+        };
+
+        let region = probe_region(pixel, width, height, PROBE_SIZE);
+        let buffers = image_resources.iter()
+            .map(|r| Self::create_readback_buffer(renderer, region[2], region[3], r.image.format))
+            .collect::<Vec<Buffer>>();
+        let mut command_buffer = renderer.create_command_buffer();
+        command_buffer.begin();
+        Self::record_image_copies(renderer, &mut command_buffer, image_resources, &buffers, Some(region));
+        command_buffer.end();
+
+        let names = image_resources.iter()
+            .enumerate()
+            .map(|(i, r)| r.name.clone().unwrap_or(i.to_string()))
+            .collect::<Vec<String>>();
+        let sender = self.inspector.sender();
+        renderer.submit_single_time_command_buffer(command_buffer, Box::new(move || {
+            let images = buffers.into_iter().zip(names)
+                .map(|(mut buffer, name)| {
+                    let data = decode_pixels(buffer.mapped(), region[2], region[3], region[2] as usize * format.bytes_per_pixel(), format)
+                        .inspect_err(|e| error!("Failed to inspect pixels: {}", e))
+                        .unwrap_or_default();
+                    ProbeImage { name, data }
+                })
+                .collect();
+            let _ = sender.send(Probe { pixel, region, images });
+        }));
+    }
+
+    /*
+     * Read back the images of the last rendered frame, writing <prefix>_<name>_<index> images and <prefix>_params.csv
+     */
+    fn dump(&self, renderer: &mut Renderer, prefix: &str) {
+        info!("Dumping images...");
+        let image_resources = self.image_resources.as_ref().unwrap();
+        let buffers = image_resources.iter()
+            .map(|r| Self::create_readback_buffer(renderer, r.image.width, r.image.height, r.image.format))
+            .collect::<Vec<Buffer>>();
+
+        let mut command_buffer = renderer.create_command_buffer();
+        command_buffer.begin();
+        Self::record_image_copies(renderer, &mut command_buffer, image_resources, &buffers, None);
+        command_buffer.end();
+
+        let images = image_resources.iter()
+            .enumerate()
+            .map(|(i, r)| (dump_name(r.name.as_deref(), i), r.image.width, r.image.height, r.image.format))
+            .collect::<Vec<(String, u32, u32, ImageFormat)>>();
+        let (format, alpha, tonemap) = (self.image_export.format, self.image_export.alpha, self.export_tonemap());
+        let animation = &self.draw_config.animation;
+        let parameters = animation.tracks.iter()
+            .zip(animation.evaluate(self.clock.time()))
+            .map(|(t, v)| format!("{},{}\n", t.name, v))
+            .collect::<String>();
+        let prefix = prefix.to_string();
+        renderer.submit_single_time_command_buffer(command_buffer, Box::new(move || {
+            thread::spawn(move || {
+                for (mut buffer, (suffix, width, height, image_format)) in buffers.into_iter().zip(images) {
+                    let output_file = format!("{}_{}.{}", prefix, suffix, format.extension());
+                    let result = PixelFormat::from_vk(image_format.vk())
+                        .and_then(|pixel_format| decode_pixels(buffer.mapped(), width, height, width as usize * pixel_format.bytes_per_pixel(), pixel_format))
+                        .and_then(|pixels| write_image(pixels, width, height, format, alpha, tonemap, &output_file));
+                    if let Err(e) = result {
+                        error!("Failed to dump image {}: {}", suffix, e);
+                    }
+                }
+
+                let output_file = format!("{}_params.csv", prefix);
+                match fs::write(&output_file, format!("name,value\n{}", parameters)) {
+                    Ok(()) => info!("Finished dumping images to {}_*", prefix),
+                    Err(e) => error!("Failed to dump parameters: {}", e),
+                }
+            });
+        }));
+    }
+
+    /*
+     * Buffer for the pixels of a @width by @height image in @format
+     */
+    fn create_readback_buffer(renderer: &mut Renderer, width: u32, height: u32, format: ImageFormat) -> Buffer {
+        Buffer::new(
+            &renderer.device,
+            &mut renderer.allocator,
+            MemoryLocation::GpuToCpu,
+            (format.bytes_per_pixel() as u64 * width as u64 * height as u64) as DeviceSize,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::TRANSFER_DST
+        )
+    }
+
+    /*
+     * Record a render of @view into @output_image and a copy of the result into @buffer
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn record_readback(&self, renderer: &mut Renderer, command_buffer: &mut CommandBuffer, image_resources: &Vec<ImageResource>, parameter_buffer: &Buffer, view: FrameView, output_image: &StorageImage, buffer: &Buffer) {
+        self.do_render(renderer, command_buffer, image_resources, parameter_buffer, view, None, None, RenderTarget {
+            image: output_image.handle(),
+            src_layout: ImageLayout::UNDEFINED,
+            dst_layout: ImageLayout::TRANSFER_SRC_OPTIMAL,
+        });
+
+        Self::record_copy(renderer, command_buffer, output_image, buffer);
+    }
+
+    /*
+     * Copy all pixels of @image, in TRANSFER_SRC_OPTIMAL layout, tightly packed into @buffer
+     */
+    fn record_copy(renderer: &Renderer, command_buffer: &mut CommandBuffer, image: &StorageImage, buffer: &Buffer) {
+        Self::record_region_copy(renderer, command_buffer, image, buffer, [0, 0, image.width, image.height]);
+    }
+
+    /*
+     * Copy the pixels of @region, given as x, y, width and height, of @image tightly packed into @buffer
+     */
+    fn record_region_copy(renderer: &Renderer, command_buffer: &mut CommandBuffer, image: &StorageImage, buffer: &Buffer, region: [u32; 4]) {
+        let [x, y, width, height] = region;
+        unsafe { renderer.device.handle().cmd_copy_image_to_buffer(
+            command_buffer.handle(),
+            *image.handle(),
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            *buffer.handle(),
+            &[
+                BufferImageCopy::default()
+                    .buffer_image_height(height)
+                    .buffer_offset(0)
+                    .image_extent(Extent3D::default().width(width).height(height).depth(1))
+                    .image_offset(Offset3D::default().x(x as i32).y(y as i32))
+                    .image_subresource(ImageSubresourceLayers::default()
+                        .layer_count(1)
+                        .mip_level(0)
+                        .aspect_mask(ImageAspectFlags::COLOR)
+                        .base_array_layer(0)
+                    )
+            ]
+        ) };
+    }
+
+    /*
+     * Write @duration seconds of audio from @start on to `<filename>.wav`
+     */
+    fn export_audio(&self, start: f32, duration: f32) {
+        info!("Exporting audio...");
+        let audio_config = self.audio_config.clone();
+        let sample_rate = self.image_export.sample_rate;
+        let output_file = format!("{}.wav", self.image_export.filename);
+        thread::spawn(move || {
+            match export_audio(&audio_config, start, duration, sample_rate, output_file.as_str()) {
+                Ok(()) => info!("Finished exporting audio to {}", output_file),
+                Err(e) => error!("Failed to export audio: {}", e),
+            }
+        });
+    }
+
+    /*
+     * Keep the audio sink in line with the clock
+     */
+    fn sync_audio(&self) {
+        if let Some(sink) = &self.sink {
+            if self.clock.is_playing() == sink.is_paused() {
+                if self.clock.is_playing() { sink.play() } else { sink.pause() }
+            }
+
+            if sink.speed() != self.clock.speed() {
+                sink.set_speed(self.clock.speed());
+            }
+
+            let seekhead = sink.get_pos();
+            let render_time = self.clock.duration();
+            if seekhead.abs_diff(render_time) > Duration::from_secs_f32(0.05) {
+                _ = Sink::try_seek(sink, render_time);
+            }
+        }
+    }
+
+    /*
+     * Perform a compute writing to @target, the last image or the image given with @visualize in its visualization
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn do_render(&self, renderer: &mut Renderer, command_buffer: &mut CommandBuffer, image_resources: &Vec<ImageResource>, parameter_buffer: &Buffer, view: FrameView, mut timestamps: Option<&mut TimestampPool>, visualize: Option<(&mut VisualizePass, usize, VisualizeSettings)>, target: RenderTarget) {
+        if let Some(timestamps) = timestamps.as_deref_mut() {
+            timestamps.reset(command_buffer);
+        }
+
+        // Clear all images with a clear config
+        {
+            for i in image_resources {
+                renderer.transition_image(
+                    command_buffer,
+                    i.image.handle(),
+                    vk::ImageLayout::GENERAL,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::AccessFlags::NONE,
+                    vk::AccessFlags::TRANSFER_WRITE
+                );
+
+                // A reset also clears the images that keep their contents
+                let clear = match &i.clear {
+                    ClearConfig::None if self.reset => Some([0.0, 0.0, 0.0, 0.0]),
+                    ClearConfig::None => None,
+                    ClearConfig::Color(r, g, b) => Some([*r, *g, *b, 1f32]),
+                };
+                match clear {
+                    None => {},
+                    Some(color) => {
+                        unsafe {
+                            renderer.device.handle()
+                                .cmd_clear_color_image(
+                                    command_buffer.handle(),
+                                    *i.image.handle(),
+                                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                                    &vk::ClearColorValue {
+                                        float32: color
+                                    },
+                                    &[vk::ImageSubresourceRange {
+                                        aspect_mask: ImageAspectFlags::COLOR,
+                                        base_mip_level: 0,
+                                        level_count: 1,
+                                        base_array_layer: 0,
+                                        layer_count: 1,
+                                    }]
+                                );
+                        }
                     }
                 }
 
                 renderer.transition_image(
-                    &command_buffer,
-                    &i.image.handle(),
+                    command_buffer,
+                    i.image.handle(),
                     vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                     vk::ImageLayout::GENERAL,
                     vk::PipelineStageFlags::TRANSFER,
@@ -236,72 +1356,102 @@ impl DrawOrchestrator {
         }
 
         // Compute images
-        let current_time = renderer.start_time.elapsed().as_secs_f32();
         for p in self.passes.as_ref().unwrap() {
-            if let Some(pipeline) = renderer.pipeline_store().get(p.pipeline_handle) {
-                command_buffer.bind_pipeline(&pipeline);
+            if let Some(pipeline) = p.pipeline.as_ref().filter(|_| p.enabled) {
+                command_buffer.bind_pipeline(pipeline);
                 let push_constants = PushConstants {
-                    time: current_time,
+                    time: view.time,
                     in_image: p.in_images.first().map(|&x| x as i32).unwrap_or(-1),
                     out_image: p.out_images.first().map(|&x| x as i32).unwrap_or(-1),
+                    _padding: 0,
+                    offset: view.offset,
+                    resolution: view.resolution,
+                    in_count: p.in_images.len() as i32,
+                    out_count: p.out_images.len() as i32,
+                    in_images: Self::image_list(&p.in_images),
+                    out_images: Self::image_list(&p.out_images),
                 };
-                command_buffer.push_constants(&pipeline, vk::ShaderStageFlags::COMPUTE, 0, &bytemuck::cast_slice(std::slice::from_ref(&push_constants)));
-                command_buffer.bind_push_descriptor_images(
-                    &pipeline,
-                    &image_resources.iter().map(|r| {
-                        &r.image
-                    }).collect::<Vec<&Image>>()
+                command_buffer.push_constants(pipeline, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::cast_slice(std::slice::from_ref(&push_constants)));
+                bind_images(command_buffer, pipeline, &image_resources.iter().map(|r| &r.image).collect::<Vec<&StorageImage>>());
+                command_buffer.bind_push_descriptor(
+                    pipeline,
+                    0,
+                    &[vk::WriteDescriptorSet::default()
+                        .dst_binding(1)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .buffer_info(&[parameter_buffer.binding()])
+                    ]
                 );
 
-                match p.dispatches {
+                let dispatch = match p.dispatches {
                     DispatchConfig::FullScreen => {
                         let width = image_resources.first().unwrap().image.width;
-                        let height = image_resources.first().unwrap().image.width;
-                        let dispatches = UVec3::new(
+                        let height = image_resources.first().unwrap().image.height;
+                        UVec3::new(
                             (width as f32 / self.workgroup_size as f32).ceil() as u32,
                             (height as f32 / self.workgroup_size as f32).ceil() as u32,
                             1
-                        );
-                        command_buffer.dispatch(dispatches.x, dispatches.y, dispatches.z);
+                        )
                     },
-                    DispatchConfig::Count(x, y, z) => {
-                        command_buffer.dispatch(x, y, z);
-                    }
-                }
-            }
-
-            // TODO: Add synchronization between passes
-        };
+                    DispatchConfig::Count(x, y, z) => UVec3::new(x, y, z),
+                };
 
-        self.sink.as_ref().map(|sink| {
-            let seekhead = sink.get_pos();
-            let render_time = renderer.start_time.elapsed();
+                let timed = match timestamps.as_deref_mut() {
+                    Some(timestamps) => timestamps.begin(command_buffer, PassSample {
+                        name: p.name.clone(),
+                        dispatch: dispatch.to_array(),
+                        memory: Self::pass_memory(p, image_resources),
+                    }),
+                    None => false,
+                };
+                command_buffer.dispatch(dispatch.x, dispatch.y, dispatch.z);
+                if let Some(timestamps) = timestamps.as_deref().filter(|_| timed) {
+                    timestamps.end(command_buffer);
+                }
 
-            if seekhead.abs_diff(render_time) > Duration::from_secs_f32(0.05) {
-                _ = Sink::try_seek(sink, render_time);
+                // The next pass reads and writes the images this one wrote
+                unsafe {
+                    renderer.device.handle().cmd_pipeline_barrier(
+                        command_buffer.handle(),
+                        vk::PipelineStageFlags::COMPUTE_SHADER,
+                        vk::PipelineStageFlags::COMPUTE_SHADER,
+                        vk::DependencyFlags::empty(),
+                        &[vk::MemoryBarrier::default()
+                            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                            .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)],
+                        &[],
+                        &[]
+                    );
+                }
             }
-        });
+        };
 
-        // Copy to target_image
+        // Copy to target
         {
-            let output_image = &image_resources.last().expect("No images found to output").image;
+            let output_image = match visualize {
+                Some((pass, image, settings)) => {
+                    let images = image_resources.iter().map(|r| &r.image).collect::<Vec<&StorageImage>>();
+                    pass.record(renderer, command_buffer, &images, image, &settings, renderer.frame_index)
+                },
+                None => &image_resources.last().expect("No images found to output").image,
+            };
 
             renderer.transition_image(
-                &command_buffer,
-                &output_image.handle(),
+                command_buffer,
+                output_image.handle(),
                 vk::ImageLayout::GENERAL,
                 vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
                 vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::TRANSFER,
-                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
                 vk::AccessFlags::TRANSFER_READ
             );
 
             // Transition the target image
             renderer.transition_image(
-                &command_buffer,
-                &target_image,
-                src_layout,
+                command_buffer,
+                target.image,
+                target.src_layout,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 vk::PipelineStageFlags::TOP_OF_PIPE,
                 vk::PipelineStageFlags::TRANSFER,
@@ -312,7 +1462,7 @@ impl DrawOrchestrator {
             unsafe {
                 renderer.device.handle().cmd_clear_color_image(
                     command_buffer.handle(),
-                    *target_image,
+                    *target.image,
                     vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                     &vk::ClearColorValue {
                         float32: [0.0, 0.0, 0.0, 1.0]
@@ -331,7 +1481,7 @@ impl DrawOrchestrator {
                     command_buffer.handle(),
                     *output_image.handle(),
                     vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                    *target_image,
+                    *target.image,
                     vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                     &[vk::ImageBlit::default()
                         .src_offsets([
@@ -363,10 +1513,10 @@ impl DrawOrchestrator {
 
             // Transfer back to default states
             renderer.transition_image(
-                &command_buffer,
-                &target_image,
+                command_buffer,
+                target.image,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                dst_layout,
+                target.dst_layout,
                 vk::PipelineStageFlags::TRANSFER,
                 vk::PipelineStageFlags::BOTTOM_OF_PIPE,
                 vk::AccessFlags::TRANSFER_WRITE,
@@ -374,7 +1524,7 @@ impl DrawOrchestrator {
             );
 
             renderer.transition_image(
-                &command_buffer,
+                command_buffer,
                 output_image.handle(),
                 vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
                 vk::ImageLayout::GENERAL,
@@ -386,39 +1536,423 @@ impl DrawOrchestrator {
         }
     }
 
-    fn create_image_resources(renderer: &mut Renderer, draw_config: &DrawConfig, width: u32, height: u32) -> Vec<ImageResource> {
-        let image_resources = draw_config.images.iter().map(|c| {
-            let image = Image::new(
+    fn push_constant_ranges() -> [vk::PushConstantRange; 1] {
+        [
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(size_of::<PushConstants>() as u32),
+        ]
+    }
+
+    /*
+     * Create the passes of the DrawConfig. Shaders of @previous passes with the same file and macros aren't compiled
+     * again, with @keep_pipelines passes also keep the pipeline of a previous pass with the same shader and
+     * specialization.
+     */
+    fn create_passes(&mut self, previous: Vec<ShaderPass>, keep_pipelines: bool) {
+        let images = (self.draw_config.images.len() as u32, self.draw_config.image_format);
+        let macros = self.draw_config.macros(self.workgroup_size);
+        let mut previous_shaders = std::mem::take(&mut self.shaders).into_iter()
+            .map(Some)
+            .collect::<Vec<Option<CompiledShader>>>();
+        // Indices of the kept shaders, from the previous to the new shaders
+        let mut kept_shaders: HashMap<usize, usize> = HashMap::new();
+
+        // Shaders, compiled once for all passes using the same file with the same defines
+        let draw_config = &self.draw_config;
+        let shader_watcher = self.shader_watcher.get_or_insert_with(ShaderWatcher::new);
+        let mut shaders: Vec<CompiledShader> = Vec::new();
+        let mut shader_indices: HashMap<(String, BTreeMap<String, String>), usize> = HashMap::new();
+        let passes = draw_config.passes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let defines = c.defines.clone().into_iter().collect::<BTreeMap<String, String>>();
+                let shader = match shader_indices.entry((c.shader.clone(), defines)) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        let mut pass_macros = macros.clone();
+                        pass_macros.extend(c.defines.clone());
+                        if !effects::is_builtin(&c.shader) {
+                            shader_watcher.watch(Path::new(&c.shader));
+                        }
+                        let kept = previous_shaders.iter()
+                            .position(|s| s.as_ref().is_some_and(|s| s.path == c.shader && s.macros == pass_macros));
+                        match kept {
+                            Some(index) => {
+                                kept_shaders.insert(index, shaders.len());
+                                shaders.push(previous_shaders[index].take().unwrap());
+                            },
+                            None => shaders.push(CompiledShader::new(&c.shader, pass_macros, images)),
+                        }
+                        *entry.insert(shaders.len() - 1)
+                    }
+                };
+
+                let pipeline = previous.iter()
+                    .filter(|_| keep_pipelines)
+                    .find(|p| kept_shaders.get(&p.shader) == Some(&shader) && p.specialization == c.specialization)
+                    .and_then(|p| p.pipeline.clone());
+
+                // References were checked by validate
+                let resolve = |images: &Vec<ImageRef>| images.iter()
+                    .map(|r| draw_config.image_index(r).unwrap())
+                    .collect::<Vec<u32>>();
+
+                let name = draw_config.pass_name(i);
+                let enabled = previous.iter().find(|p| p.name == name).is_none_or(|p| p.enabled);
+
+                ShaderPass {
+                    name,
+                    dispatches: c.dispatches,
+                    in_images: resolve(&c.input_resources),
+                    out_images: resolve(&c.output_resources),
+                    shader,
+                    specialization: c.specialization.clone(),
+                    rebuild: pipeline.is_none(),
+                    pipeline,
+                    enabled,
+                }
+            })
+            .collect::<Vec<ShaderPass>>();
+
+        self.passes = Some(passes);
+        self.shaders = shaders;
+    }
+
+    /*
+     * Switch to @draw_config while running. Unchanged shaders and pipelines are kept, so are the contents of images
+     * with the same name, or at the same index when unnamed. The clock keeps running.
+     */
+    fn apply_config(&mut self, renderer: &mut Renderer, draw_config: DrawConfig) {
+        if let Err(e) = Self::validate(&draw_config, &self.audio_config) {
+            error!("Ignoring new DrawConfig: {}", e);
+            return;
+        }
+
+        // Resources of the previous config may still be used by frames in flight
+        if let Err(e) = unsafe { renderer.device.handle().device_wait_idle() } {
+            error!("Failed to apply new DrawConfig: {}", e);
+            return;
+        }
+
+        // New images and the layout for a new image count are created before anything is replaced
+        let diff = ConfigDiff::new(&self.draw_config, &draw_config);
+        let image_count = draw_config.images.len() as u32;
+        let extent = renderer.swapchain.get_extent();
+        let created = Self::create_image_resources(renderer, &diff.added(&draw_config.images), draw_config.image_format, extent.width, extent.height)
+            .and_then(|added| {
+                let layout = if diff.layout_changed { Some(Self::create_descriptor_layout(renderer, image_count)?) } else { None };
+                Ok((added, layout))
+            });
+        let (added, layout) = match created {
+            Ok(created) => created,
+            Err(e) => {
+                error!("Ignoring new DrawConfig: {}", e);
+                return;
+            }
+        };
+
+        self.draw_config = draw_config;
+        let mut image_resources = diff.arrange(self.image_resources.take().unwrap_or_default(), added);
+        for (image, config) in image_resources.iter_mut().zip(&self.draw_config.images) {
+            image.clear = config.clear.clone();
+        }
+        info!("Applying new DrawConfig, kept {} of {} images", diff.kept(), image_count);
+        self.image_resources = Some(image_resources);
+
+        if let Some(layout) = layout {
+            self.visualize_pass = None;
+            self.compute_descriptor_set_layout = Some(layout);
+        }
+
+        if diff.parameters_changed {
+            self.parameter_buffers = Some(Self::create_parameter_buffers(renderer, self.parameter_count()));
+        }
+
+        let passes = self.passes.take().unwrap_or_default();
+        self.create_passes(passes, !diff.layout_changed);
+        self.rebuild_pipelines(renderer);
+    }
+
+    /*
+     * Timestamp queries for this frame while the profiler is open. The timings recorded in them by the previous use
+     * of this frame slot are added to the profiler, that frame is no longer in flight.
+     */
+    fn take_timestamp_pool(&mut self, renderer: &mut Renderer) -> Option<TimestampPool> {
+        let (period, valid_bits) = self.timestamps?;
+        let pools = self.timestamp_pools.get_or_insert_with(|| (0..renderer.swapchain.get_image_count()).map(|_| None).collect());
+        let pool = pools[renderer.frame_index].take();
+        if let Some(timings) = pool.as_ref().and_then(|p| p.read()) {
+            self.profiler.add_frame(timings);
+        }
+        if !self.profiler.open {
+            return None;
+        }
+
+        let passes = self.passes.as_ref().map(|p| p.len()).unwrap_or(0).max(1) as u32;
+        match pool {
+            Some(pool) if pool.capacity() >= passes => Some(pool),
+            _ => TimestampPool::new(&renderer.device, passes, period, valid_bits)
+                .inspect_err(|e| error!("Failed to create timestamp queries: {}", e))
+                .ok(),
+        }
+    }
+
+    /*
+     * Pass visualizing the shown image and its settings, None when the output is shown as rendered. The statistics
+     * recorded by the previous use of this frame slot are handed to the visualizer.
+     */
+    fn take_visualize_pass(&mut self, renderer: &mut Renderer) -> Option<(VisualizePass, usize, VisualizeSettings)> {
+        let image_resources = self.image_resources.as_ref()?;
+        let (image, settings) = self.visualizer.current(image_resources.len(), self.draw_config.image_format.is_float())?;
+        let (count, width, height) = (image_resources.len() as u32, image_resources[0].image.width, image_resources[0].image.height);
+        let format = self.draw_config.image_format;
+
+        let mut pass = match self.visualize_pass.take() {
+            Some(pass) if pass.matches(count, width, height, format) => pass,
+            _ => VisualizePass::new(renderer, count, width, height, format)
+                .inspect_err(|e| error!("Failed to create the image visualization: {}", e))
+                .ok()?,
+        };
+        if let Some(stats) = pass.read(renderer.frame_index) {
+            self.visualizer.set_stats(stats);
+        }
+        Some((pass, image, settings))
+    }
+
+    /*
+     * Bytes of the images @pass reads and writes
+     */
+    fn pass_memory(pass: &ShaderPass, image_resources: &[ImageResource]) -> u64 {
+        let mut images = pass.in_images.iter().chain(&pass.out_images).copied().collect::<Vec<u32>>();
+        images.sort();
+        images.dedup();
+        images.iter().map(|&i| image_resources[i as usize].image.memory()).sum()
+    }
+
+    /*
+     * Recompile the shaders whose files changed, a shader that fails to compile keeps its last working version
+     */
+    fn reload_shaders(&mut self) {
+        let mut changed = match &self.shader_watcher {
+            Some(watcher) => watcher.changed(),
+            None => return,
+        };
+        changed.append(&mut self.saved_shaders);
+        let images = (self.draw_config.images.len() as u32, self.draw_config.image_format);
+
+        for (index, shader) in self.shaders.iter_mut().enumerate() {
+            if !changed.contains(&shader.canonical_path) {
+                continue;
+            }
+
+            info!("Reloading shader: {}", shader.path);
+            if shader.compile(images) {
+                self.passes.iter_mut().flatten()
+                    .filter(|p| p.shader == index)
+                    .for_each(|p| p.rebuild = true);
+            }
+        }
+    }
+
+    /*
+     * Create the pipelines of passes marked for a rebuild, without compiling their shaders
+     */
+    fn rebuild_pipelines(&mut self, renderer: &mut Renderer) {
+        let layout = match &self.compute_descriptor_set_layout {
+            Some(layout) => layout.clone(),
+            None => return,
+        };
+
+        for pass in self.passes.iter_mut().flatten().filter(|p| p.rebuild) {
+            pass.rebuild = false;
+            // Shaders that never compiled get their pipeline once they do
+            if self.shaders[pass.shader].code.is_empty() {
+                continue;
+            }
+            match ComputePipeline::new(&renderer.device, &self.shaders[pass.shader].code, &layout, &Self::push_constant_ranges(), &pass.specialization) {
+                Ok(pipeline) => pass.pipeline = Some(pipeline),
+                Err(e) => error!("Pass {}: {}", pass.name, e),
+            }
+        }
+    }
+
+    /*
+     * Editors for the specialization constants of all passes
+     */
+    fn specialization_editor(&mut self, ui: &mut egui::Ui) {
+        let passes = self.passes.iter_mut().flatten().filter(|p| !p.specialization.is_empty()).collect::<Vec<&mut ShaderPass>>();
+        if passes.is_empty() {
+            ui.label("No passes with specialization constants");
+        }
+
+        for pass in passes {
+            ui.label(&pass.name);
+            for constant in pass.specialization.iter_mut() {
+                let changed = ui.horizontal(|ui| {
+                    ui.label(&constant.name);
+                    match &mut constant.value {
+                        SpecValue::Bool(v) => ui.checkbox(v, ""),
+                        SpecValue::Int(v) => ui.add(egui::DragValue::new(v)),
+                        SpecValue::UInt(v) => ui.add(egui::DragValue::new(v)),
+                        SpecValue::Float(v) => ui.add(egui::DragValue::new(v).speed(0.01)),
+                    }.changed()
+                }).inner;
+                pass.rebuild |= changed;
+            }
+        }
+    }
+
+    /*
+     * Pad the image indices of a pass with -1 to the fixed push constant array size
+     */
+    fn image_list(images: &[u32]) -> [i32; MAX_PASS_IMAGES] {
+        let mut list = [-1; MAX_PASS_IMAGES];
+        list.iter_mut().zip(images).for_each(|(l, &i)| *l = i as i32);
+        list
+    }
+
+    /*
+     * Parameter buffer holding @size floats
+     */
+    fn create_parameter_buffer(renderer: &mut Renderer, size: usize) -> Buffer {
+        Buffer::new(
+            &renderer.device,
+            &mut renderer.allocator,
+            MemoryLocation::CpuToGpu,
+            (size_of::<f32>() * size.max(1)) as DeviceSize,
+            BufferUsageFlags::STORAGE_BUFFER
+        )
+    }
+
+    /*
+     * One parameter buffer per frame in flight
+     */
+    fn create_parameter_buffers(renderer: &mut Renderer, size: usize) -> Vec<Buffer> {
+        (0..renderer.swapchain.get_image_count())
+            .map(|_| Self::create_parameter_buffer(renderer, size))
+            .collect()
+    }
+
+    /*
+     * Floats in the parameter buffer, see parameters
+     */
+    fn parameter_count(&self) -> usize {
+        self.draw_config.animation.tracks.len() + INPUT_SIZE + self.user_data.len()
+    }
+
+    /*
+     * Push descriptor layout of all passes, @image_count storage images at binding 0 and the parameters at binding 1
+     */
+    fn create_descriptor_layout(renderer: &Renderer, image_count: u32) -> Result<Arc<PushDescriptorLayout>, Error> {
+        let layout_bindings = &[
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(image_count)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::FRAGMENT),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        ];
+        Ok(Arc::new(PushDescriptorLayout::new(&renderer.device, layout_bindings)?))
+    }
+
+    /*
+     * Contents of the parameter buffer: the @animated parameters followed by the @input state and the data uploaded
+     * by the frame hook
+     */
+    fn parameters(&self, animated: Vec<f32>, input: &InputState) -> Vec<f32> {
+        let mut values = animated;
+        values.extend(input.values());
+        values.extend(&self.user_data);
+        values
+    }
+
+    /*
+     * Let the frame hook update this frame, @animated holds the parameter values it may override
+     */
+    fn run_frame_hook(&mut self, animated: &mut Vec<f32>) {
+        let Some(mut hook) = self.frame_hook.take() else {
+            return;
+        };
+
+        let time = self.clock.time();
+        let parameters = self.draw_config.animation.tracks.iter()
+            .map(|t| t.name.clone())
+            .zip(animated.iter().copied())
+            .collect();
+        let passes = self.passes.iter().flatten()
+            .map(|p| (p.name.clone(), p.enabled))
+            .collect();
+        let mut context = FrameContext::new(time, time - self.hook_time, self.frame_count, &self.input, parameters, passes, &mut self.user_data);
+        hook.update(&mut context);
+        let changes = context.finish();
+
+        self.hook_time = time;
+        *animated = changes.parameters.into_iter().map(|(_, v)| v).collect();
+        for (pass, (_, enabled)) in self.passes.iter_mut().flatten().zip(changes.passes) {
+            pass.enabled = enabled;
+        }
+        changes.actions.into_iter().for_each(|action| self.perform(action));
+        self.frame_hook = Some(hook);
+    }
+
+    fn write_parameters(buffer: &mut Buffer, values: &[f32]) {
+        let bytes = bytemuck::cast_slice::<f32, u8>(values);
+        buffer.mapped()[..bytes.len()].copy_from_slice(bytes);
+    }
+
+    /*
+     * Images of @width by @height in @format for the configs in @images, in the GENERAL layout
+     */
+    fn create_image_resources(renderer: &mut Renderer, images: &[ImageConfig], format: ImageFormat, width: u32, height: u32) -> Result<Vec<ImageResource>, Error> {
+        let image_resources = images.iter().map(|c| {
+            let image = StorageImage::new(
                 &renderer.device,
-                &mut renderer.allocator,
+                &renderer.allocator,
                 width,
                 height,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST
-            );
+                format,
+                vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST
+            )?;
 
-            ImageResource {
+            Ok(ImageResource {
+                name: c.name.clone(),
                 image,
                 clear: c.clear.clone(),
-            }
-        }).collect::<Vec<ImageResource>>();
+            })
+        }).collect::<Result<Vec<ImageResource>, Error>>()?;
 
         // Transition images
         let mut image_command_buffer = CommandBuffer::new(&renderer.device, &renderer.command_pool);
         image_command_buffer.begin();
         {
             for image_resource in &image_resources {
-                renderer.transition_image(&image_command_buffer, &image_resource.image.handle(), vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::BOTTOM_OF_PIPE, vk::AccessFlags::empty(), vk::AccessFlags::empty());
+                renderer.transition_image(&image_command_buffer, image_resource.image.handle(), vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::BOTTOM_OF_PIPE, vk::AccessFlags::empty(), vk::AccessFlags::empty());
             }
         }
         image_command_buffer.end();
         renderer.submit_single_time_command_buffer(image_command_buffer, Box::new(|| {}));
 
-        image_resources
+        Ok(image_resources)
     }
 }
 
 impl GuiComponent for DrawOrchestrator {
     fn gui(&mut self, context: &Context) {
+        if let Some(failure) = &self.failure {
+            egui::CentralPanel::default().show(context, |ui| {
+                ui.label(RichText::new(format!("Failed to start: {}", failure)).color(Color32::LIGHT_RED));
+                ui.label("Close the window to exit");
+            });
+            return;
+        }
+
         TopBottomPanel::top("top").show(context, |ui| {
             menu::bar(ui, |ui| {
                 ui.menu_button("Export..", |ui| {
@@ -432,110 +1966,426 @@ impl GuiComponent for DrawOrchestrator {
                         ui.add(egui::DragValue::new(&mut self.image_export.width));
                         ui.add(egui::DragValue::new(&mut self.image_export.height));
                     });
-                    if ui.button("Save").clicked() {
-                        self.image_export.do_export = true;
+                    let export = &mut self.image_export;
+                    let float_images = self.draw_config.image_format.is_float();
+                    egui::ComboBox::from_label("Format")
+                        .selected_text(export.format.name())
+                        .show_ui(ui, |ui| {
+                            for format in ExportFormat::ALL {
+                                let supported = float_images || !format.needs_float_images();
+                                let selected = ui.add_enabled(supported, egui::SelectableLabel::new(export.format == format, format.name()))
+                                    .on_disabled_hover_text("Needs a float image format in the DrawConfig")
+                                    .clicked();
+                                if selected {
+                                    export.format = format;
+                                }
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut export.supersampling).range(1..=8).prefix("supersampling: ").suffix("x"));
+                        egui::ComboBox::from_id_salt("filter")
+                            .selected_text(format!("{:?}", export.filter))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut export.filter, DownsampleFilter::Box, "Box");
+                                ui.selectable_value(&mut export.filter, DownsampleFilter::Triangle, "Triangle");
+                            });
+                    });
+                    ui.add_enabled(export.format.supports_alpha(), egui::Checkbox::new(&mut export.alpha, "Alpha"));
+                    ui.add_enabled_ui(float_images && !export.format.is_float(), |ui| {
+                        egui::ComboBox::from_label("Tonemap")
+                            .selected_text(export.tonemap.name())
+                            .show_ui(ui, |ui| {
+                                for tonemap in Tonemap::ALL {
+                                    ui.selectable_value(&mut export.tonemap, tonemap, tonemap.name());
+                                }
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut export.tiled, "Tiled");
+                        ui.add_enabled(export.tiled, egui::DragValue::new(&mut export.tile_size).range(64..=16384).prefix("tile: "));
+                        ui.add_enabled(export.tiled, egui::DragValue::new(&mut export.tile_overlap).range(0..=1024).prefix("overlap: "));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut export.use_current_time, "Current time");
+                        ui.add_enabled(!export.use_current_time, egui::DragValue::new(&mut export.time).speed(0.01).range(0.0..=f32::MAX).suffix("s"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut export.frames).range(1..=1_000_000).prefix("frames: "));
+                        ui.add_enabled(export.frames > 1 || export.audio, egui::DragValue::new(&mut export.fps).speed(0.1).range(1.0..=1000.0).suffix(" fps"));
+                    });
+                    // Stepping through the timeline moves by one exported frame
+                    self.clock.set_frame_rate(export.fps);
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut export.audio, "Audio");
+                        ui.add_enabled(export.audio, egui::DragValue::new(&mut export.sample_rate).range(8000..=192000).suffix("Hz"));
+                    });
+                    ui.horizontal(|ui| {
+                        match self.image_export.sequence {
+                            Some((_, frame)) => {
+                                ui.add_enabled(false, egui::Button::new("Save"));
+                                ui.label(format!("Frame {} of {}", frame + 1, self.image_export.frames));
+                            },
+                            None => if ui.button("Save").clicked() {
+                                self.image_export.do_export = true;
+                            },
+                        }
+                        if ui.button("Dump all images").clicked() {
+                            self.dump_request = Some(self.image_export.filename.clone());
+                        }
+                    });
+                });
+                ui.menu_button("Animation", |ui| {
+                    ui.checkbox(&mut self.curve_editor.open, "Curve editor");
+                });
+                ui.menu_button("Images", |ui| {
+                    ui.checkbox(&mut self.inspector.open, "Pixel inspector");
+                    ui.separator();
+                    let names = (0..self.draw_config.images.len()).map(|i| self.draw_config.image_name(i)).collect::<Vec<String>>();
+                    self.visualizer.menu(ui, &names, self.draw_config.image_format.is_float());
+                });
+                ui.menu_button("Passes", |ui| {
+                    ui.checkbox(&mut self.shader_editor.open, "Shader editor");
+                    ui.add_enabled(self.timestamps.is_some(), egui::Checkbox::new(&mut self.profiler.open, "Profiler"));
+                    ui.separator();
+                    for pass in self.passes.iter_mut().flatten() {
+                        ui.checkbox(&mut pass.enabled, &pass.name);
                     }
+                    ui.separator();
+                    self.specialization_editor(ui);
                 });
+                ui.separator();
+                self.timeline(ui);
             });
         });
+
+        self.curve_editor.show(context, &mut self.draw_config.animation, &mut self.clock, self.timeline_length);
+        let errors = self.shaders.iter().filter_map(|s| s.error.as_ref()).collect::<Vec<&ShaderError>>();
+        let passes = self.draw_config.passes.iter().enumerate()
+            .map(|(i, p)| (self.draw_config.pass_name(i), p.shader.clone()))
+            .collect::<Vec<(String, String)>>();
+        if let Some(path) = self.shader_editor.show(context, &passes, &errors) {
+            self.saved_shaders.push(canonical(Path::new(&path)));
+        }
+        shader_errors::show(context, &errors);
+        let image_memory = self.image_resources.iter().flatten().map(|r| r.image.memory()).sum();
+        self.profiler.show(context, image_memory);
+        self.inspector.show(context);
+        self.visualizer.show(context, self.draw_config.images.len());
+
+        // Input for the shaders and key bindings
+        read_egui(&mut self.input, context);
+        let mut bound = Vec::new();
+        if let Some(key_bindings) = &mut self.key_bindings {
+            let mut actions = Vec::new();
+            for key in self.input.pressed_keys() {
+                let key_actions = key_bindings.key_pressed(key, &self.input);
+                if !key_actions.is_empty() {
+                    bound.push(key);
+                }
+                actions.extend(key_actions);
+            }
+            actions.into_iter().for_each(|action| self.perform(action));
+        }
+
+        // Transport shortcuts, unless turned off or a key binding acted on the key
+        if self.transport_keys && !context.wants_keyboard_input() {
+            let pressed = |key| self.input.key_pressed(key) && !bound.contains(&key);
+            if pressed(KEY_SPACE) {
+                self.clock.toggle();
+            }
+            if pressed(KEY_LEFT) {
+                self.clock.step(-1);
+            }
+            if pressed(KEY_RIGHT) {
+                self.clock.step(1);
+            }
+            if pressed(KEY_HOME) {
+                self.clock.seek(self.clock.loop_region().map(|(start, _)| start).unwrap_or(0.0));
+            }
+        }
     }
 }
 
-impl RenderComponent for DrawOrchestrator {
-    fn initialize(&mut self, renderer: &mut Renderer)
-    {
-        let image_count = self.draw_config.images.len() as u32;
+impl DrawOrchestrator {
+    /*
+     * Transport controls and timeline scrubber
+     */
+    fn timeline(&mut self, ui: &mut egui::Ui) {
+        if ui.button(if self.clock.is_playing() { "⏸" } else { "▶" }).clicked() {
+            self.clock.toggle();
+        }
+        if ui.button("⏮").clicked() {
+            self.clock.step(-1);
+        }
+        if ui.button("⏭").clicked() {
+            self.clock.step(1);
+        }
+
+        self.timeline_length = self.timeline_length.max(self.clock.time());
+        let mut time = self.clock.time();
+        if ui.add(egui::Slider::new(&mut time, 0.0..=self.timeline_length).suffix("s")).changed() {
+            self.clock.seek(time);
+        }
+
+        let mut speed = self.clock.speed();
+        if ui.add(egui::DragValue::new(&mut speed).speed(0.01).range(0.0..=16.0).prefix("speed: ").suffix("x")).changed() {
+            self.clock.set_speed(speed);
+        }
 
-        // Verify max referred index
-        let max_reffered_image = self.draw_config.passes.iter()
-            .map(|p| p.output_resources.iter())
-            .flatten().max().unwrap_or(&0);
-        if *max_reffered_image as i32 > image_count as i32 - 1 {
-            error!("Image index out of bounds, provide enough image resources");
-            panic!("Image index out of bounds, provide enough image resources");
+        let mut looping = self.clock.loop_region().is_some();
+        let (mut loop_start, mut loop_end) = self.clock.loop_region().unwrap_or((0.0, self.timeline_length));
+        let mut changed = ui.checkbox(&mut looping, "Loop").changed();
+        if looping {
+            changed |= ui.add(egui::DragValue::new(&mut loop_start).speed(0.1).range(0.0..=loop_end).suffix("s")).changed();
+            changed |= ui.add(egui::DragValue::new(&mut loop_end).speed(0.1).range(loop_start..=f32::MAX).suffix("s")).changed();
+        }
+        if changed {
+            self.clock.set_loop_region(looping.then_some((loop_start, loop_end)));
         }
+    }
+
+    /*
+     * Create the images, layouts and buffers of the config once the renderer is up
+     */
+    fn setup(&mut self, renderer: &mut Renderer) -> Result<(), Error> {
+        let image_count = self.draw_config.images.len() as u32;
 
         // Layout
-        let layout_bindings = &[
-            vk::DescriptorSetLayoutBinding::default()
-                .binding(0)
-                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-                .descriptor_count(image_count)
-                .stage_flags(vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::FRAGMENT)
-        ];
-        let compute_descriptor_set_layout = DescriptorSetLayout::new_push_descriptor(
-            &renderer.device,
-            layout_bindings
-        );
+        let compute_descriptor_set_layout = Self::create_descriptor_layout(renderer, image_count)?;
 
         // Images
-        let image_resources = Self::create_image_resources(renderer, &self.draw_config, renderer.swapchain.get_extent().width, renderer.swapchain.get_extent().height);
+        let extent = renderer.swapchain.get_extent();
+        let image_resources = Self::create_image_resources(renderer, &self.draw_config.images, self.draw_config.image_format, extent.width, extent.height)?;
 
-        let push_constant_ranges = Vec::from([
-            vk::PushConstantRange::default()
-                .stage_flags(vk::ShaderStageFlags::COMPUTE)
-                .offset(0)
-                .size(size_of::<PushConstants>() as u32),
-        ]);
+        self.workgroup_size = WORKGROUP_SIZE;
 
-        self.workgroup_size = 32;
-        let mut macros: HashMap<String, String> = HashMap::new();
-        macros.insert("NUM_IMAGES".to_string(), image_count.to_string());
-        macros.insert("WORKGROUP_SIZE".to_string(), self.workgroup_size.to_string());
+        // Passes are timed with timestamps for the profiler
+        let limits = unsafe { renderer.instance.handle().get_physical_device_properties(renderer.physical_device) }.limits;
+        let queue_families = unsafe { renderer.instance.handle().get_physical_device_queue_family_properties(renderer.physical_device) };
+        let valid_bits = queue_families.get(renderer.device.inner.queue_family_index as usize)
+            .map(|f| f.timestamp_valid_bits)
+            .unwrap_or(0);
+        if limits.timestamp_compute_and_graphics == vk::TRUE && valid_bits > 0 {
+            self.timestamps = Some((limits.timestamp_period, valid_bits));
+        } else {
+            warn!("The device doesn't support timestamps, the profiler is disabled");
+        }
 
-        // Passes
-        let passes = self.draw_config.passes
-            .iter()
-            .map(|c| {
-                let pipeline_handle = renderer.pipeline_store().insert(
-                    PipelineConfig {
-                        shader_path: c.shader.clone().into(),
-                        descriptor_set_layouts: vec![compute_descriptor_set_layout.clone()],
-                        push_constant_ranges: push_constant_ranges.clone(),
-                        macros: macros.clone()
-                    }
-                )?;
+        // Parameter buffers, one per frame in flight
+        let parameter_buffers = Self::create_parameter_buffers(renderer, self.parameter_count());
 
-                Ok(ShaderPass {
-                    pipeline_handle,
-                    dispatches: c.dispatches,
-                    in_images: c.input_resources.clone(),
-                    out_images: c.output_resources.clone(),
-                })
-            })
-            .collect::<Result<Vec<ShaderPass>, PipelineErr>>()
-            .inspect_err(|err| {
-                error!("{}", err);
-                exit(0);
-            })
-            .unwrap();
+        // Start the clock once setup is done
+        self.clock = Clock::new();
 
         self.compute_descriptor_set_layout = Some(compute_descriptor_set_layout);
         self.image_resources = Some(image_resources);
-        self.passes = Some(passes);
+        self.shader_watcher = Some(ShaderWatcher::new());
+        self.create_passes(Vec::new(), false);
+        self.rebuild_pipelines(renderer);
+        self.parameter_buffers = Some(parameter_buffers);
 
-        // Audio things
-        if let AudioFile(file) = self.audio_config.clone() {
-            let (stream, stream_handle) = OutputStream::try_default().unwrap();
-            self.audio_stream = Some(stream);
-            self.sink = Some(Sink::try_new(&stream_handle).unwrap());
-            // Load a sound from a file, using a path relative to Cargo.toml
-            let file = BufReader::new(File::open(file).unwrap());
-            // Decode that sound file into a source
-            let source = Decoder::new(file).unwrap();
-
-            self.sink.as_ref().map(|sink| Sink::append(sink, source));
-            self.sink.as_ref().map(|sink| Sink::play(sink));
-        };
+        // Audio things, a missing audio device shouldn't stop the visuals
+        if let Err(e) = self.start_audio() {
+            warn!("{}, continuing without audio", e);
+        }
+
+        Ok(())
+    }
+}
+
+impl RenderComponent for DrawOrchestrator {
+    fn initialize(&mut self, renderer: &mut Renderer)
+    {
+        // cen can't be stopped from here, the error is shown until the window is closed and then returned by App::run
+        if let Err(err) = self.setup(renderer) {
+            error!("{}", err);
+            self.failure = Some(err);
+        }
     }
 
     fn render(&mut self, renderer: &mut Renderer, command_buffer: &mut CommandBuffer, swapchain_image: &vk::Image, _view: &vk::ImageView) {
+        if self.failure.is_some() {
+            return;
+        }
+
+        if self.frame_count > 0 {
+            if let Some(prefix) = self.dump_request.take() {
+                self.dump(renderer, &prefix);
+            }
+        }
+        self.frame_count += 1;
+
+        self.clock.update();
+        self.sync_audio();
+        if let Some(draw_config) = self.config_updates.as_ref().and_then(|r| r.try_iter().last()) {
+            self.apply_config(renderer, draw_config);
+        }
+        self.reload_shaders();
+        self.rebuild_pipelines(renderer);
 
         if self.image_export.do_export {
-            self.export(renderer, self.image_export.width, self.image_export.height);
+            self.start_export();
             self.image_export.do_export = false;
         }
+        self.export_next_frame(renderer);
+        self.probe(renderer);
+
+        let mut animated = self.draw_config.animation.evaluate(self.clock.time());
+        self.run_frame_hook(&mut animated);
+
+        // Upload this frame's parameters, the buffer of the previous use of this frame slot is no longer in flight.
+        // It grows along with the data of the frame hook.
+        let parameters = self.parameters(animated, &self.input);
+        let parameter_buffer = &mut self.parameter_buffers.as_mut().unwrap()[renderer.frame_index];
+        if (parameter_buffer.size as usize) < size_of::<f32>() * parameters.len() {
+            *parameter_buffer = Self::create_parameter_buffer(renderer, parameters.len());
+        }
+        Self::write_parameters(parameter_buffer, &parameters);
+
+        let mut timestamps = self.take_timestamp_pool(renderer);
+        let mut visualize = self.take_visualize_pass(renderer);
+        let parameter_buffer = &self.parameter_buffers.as_ref().unwrap()[renderer.frame_index];
+        let image_resources = self.image_resources.as_ref().unwrap();
+        let view = FrameView {
+            time: self.clock.time(),
+            offset: [0.0, 0.0],
+            resolution: [image_resources[0].image.width as f32, image_resources[0].image.height as f32],
+        };
+        let shown = visualize.as_mut().map(|(pass, image, settings)| (pass, *image, *settings));
+        self.do_render(renderer, command_buffer, image_resources, parameter_buffer, view, timestamps.as_mut(), shown, RenderTarget {
+            image: swapchain_image,
+            src_layout: ImageLayout::PRESENT_SRC_KHR,
+            dst_layout: ImageLayout::PRESENT_SRC_KHR,
+        });
+        self.reset = false;
+        if let Some(pools) = &mut self.timestamp_pools {
+            pools[renderer.frame_index] = timestamps;
+        }
+        if let Some((pass, _, _)) = visualize {
+            self.visualize_pass = Some(pass);
+        }
+    }
+}
+
+/*
+ * Name of image @index in a dump, `<name>_<index>` or `image_<index>` when it has no name
+ */
+fn dump_name(name: Option<&str>, index: usize) -> String {
+    format!("{}_{}", name.unwrap_or("image"), index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::effects::Tonemapper;
+    use crate::app::keyframes::Track;
+
+    fn validate(config: DrawConfig) -> Result<(), Error> {
+        DrawOrchestrator::validate(&config, &AudioConfig::None)
+    }
+
+    fn effects(format: ImageFormat) -> DrawConfig {
+        DrawConfig::builder()
+            .image_format(format)
+            .images(["scene", "bright", "scratch", "output"].map(ImageConfig::new))
+            .passes(effects::bloom("bloom", "scene", "bright", "scratch", "output", 0.6, 8.0, 0.5))
+            .pass(effects::tonemap("tonemap", "output", "scene", Tonemapper::Aces, 1.0))
+            .pass(effects::fxaa("fxaa", "scene", "output"))
+            .build()
+    }
+
+    #[test]
+    fn effects_validate_on_float_images() {
+        validate(effects(ImageFormat::Rgba16Float)).unwrap();
+    }
+
+    #[test]
+    fn hdr_effects_need_float_images() {
+        let error = validate(effects(ImageFormat::Rgba8)).unwrap_err().to_string();
+        assert!(error.contains("Pass bloom threshold needs a float image format"), "{}", error);
+
+        let config = DrawConfig::builder()
+            .images(["scene", "output"].map(ImageConfig::new))
+            .pass(effects::fxaa("fxaa", "scene", "output"))
+            .pass(effects::film_grain("grain", "output", "scene", 0.1))
+            .build();
+        validate(config).unwrap();
+    }
+
+    #[test]
+    fn pass_names_are_unique() {
+        let config = DrawConfig::builder()
+            .image_format(ImageFormat::Rgba16Float)
+            .images(["a", "b", "c"].map(ImageConfig::new))
+            .passes(effects::gaussian_blur("blur", "a", "b", "c", 2.0))
+            .passes(effects::gaussian_blur("blur", "c", "b", "a", 2.0))
+            .build();
+        let error = validate(config).unwrap_err().to_string();
+        assert!(error.contains("Multiple passes are named \"blur horizontal\""), "{}", error);
+
+        let config = DrawConfig::builder()
+            .images(["a", "b", "c"].map(ImageConfig::new))
+            .passes(effects::gaussian_blur("blur", "a", "b", "c", 2.0))
+            .passes(effects::gaussian_blur("blur again", "c", "b", "a", 2.0))
+            .build();
+        validate(config).unwrap();
+    }
+
+    #[test]
+    fn image_macros_are_unique() {
+        let config = DrawConfig::builder()
+            .images(["scene color", "scene-color"].map(ImageConfig::new))
+            .pass(effects::fxaa("fxaa", 0, 1))
+            .build();
+        let error = validate(config).unwrap_err().to_string();
+        assert!(error.contains("Images \"scene color\" and \"scene-color\" both map to the shader macro IMG_SCENE_COLOR"), "{}", error);
+
+        let config = DrawConfig::builder()
+            .images(["scene", "Scene"].map(ImageConfig::new))
+            .pass(effects::fxaa("fxaa", 0, 1))
+            .build();
+        assert!(validate(config).is_err());
+    }
+
+    #[test]
+    fn parameter_macros_are_unique() {
+        let mut animation = Animation::default();
+        animation.tracks.push(Track::new("glow.size"));
+        animation.tracks.push(Track::new("glow size"));
+        let config = DrawConfig::builder()
+            .images(["a", "b"].map(ImageConfig::new))
+            .pass(effects::fxaa("fxaa", "a", "b"))
+            .animation(animation)
+            .build();
+        let error = validate(config).unwrap_err().to_string();
+        assert!(error.contains("Parameters \"glow.size\" and \"glow size\" both map to the shader macro PARAM_GLOW_SIZE"), "{}", error);
+    }
+
+    #[test]
+    fn kiyo_macros_are_listed_once() {
+        let config = DrawConfig::builder()
+            .images(["num", "images"].map(ImageConfig::new))
+            .pass(effects::fxaa("fxaa", 0, 1))
+            .build();
+        let macros = config.macro_list(WORKGROUP_SIZE);
+        assert_eq!(macros.len(), config.macros(WORKGROUP_SIZE).len());
+        validate(config).unwrap();
+    }
+
+    #[test]
+    fn image_names_are_unique() {
+        let config = DrawConfig::builder()
+            .images(["a", "a"].map(ImageConfig::new))
+            .pass(effects::fxaa("fxaa", 0, 1))
+            .build();
+        let error = validate(config).unwrap_err().to_string();
+        assert!(error.contains("Multiple images are named \"a\""), "{}", error);
+    }
 
-        self.do_render(renderer, command_buffer, self.image_resources.as_ref().unwrap(), swapchain_image, ImageLayout::PRESENT_SRC_KHR, ImageLayout::PRESENT_SRC_KHR);
+    #[test]
+    fn dumps_are_named_after_their_image() {
+        assert_eq!(dump_name(Some("scene"), 0), "scene_0");
+        assert_eq!(dump_name(Some("blur"), 3), "blur_3");
+        assert_eq!(dump_name(None, 2), "image_2");
     }
 }
@@ -0,0 +1,161 @@
+use crate::app::draw_orch::{ImageRef, Pass};
+use crate::app::pipeline::{SpecConstant, SpecValue};
+
+/// Shader paths starting with this refer to shaders compiled into kiyo instead of files
+pub const BUILTIN_PREFIX: &str = "kiyo:";
+
+const GAUSSIAN_BLUR: &str = "kiyo:effects/gaussian_blur.comp";
+const BLOOM_THRESHOLD: &str = "kiyo:effects/bloom_threshold.comp";
+const BLOOM_COMPOSITE: &str = "kiyo:effects/bloom_composite.comp";
+const TONEMAP: &str = "kiyo:effects/tonemap.comp";
+const FXAA: &str = "kiyo:effects/fxaa.comp";
+const CHROMATIC_ABERRATION: &str = "kiyo:effects/chromatic_aberration.comp";
+const FILM_GRAIN: &str = "kiyo:effects/film_grain.comp";
+const COLOR_GRADE: &str = "kiyo:effects/color_grade.comp";
+const IDENTITY_LUT: &str = "kiyo:effects/identity_lut.comp";
+
+const SOURCES: [(&str, &str); 9] = [
+    (GAUSSIAN_BLUR, include_str!("shaders/effects/gaussian_blur.comp")),
+    (BLOOM_THRESHOLD, include_str!("shaders/effects/bloom_threshold.comp")),
+    (BLOOM_COMPOSITE, include_str!("shaders/effects/bloom_composite.comp")),
+    (TONEMAP, include_str!("shaders/effects/tonemap.comp")),
+    (FXAA, include_str!("shaders/effects/fxaa.comp")),
+    (CHROMATIC_ABERRATION, include_str!("shaders/effects/chromatic_aberration.comp")),
+    (FILM_GRAIN, include_str!("shaders/effects/film_grain.comp")),
+    (COLOR_GRADE, include_str!("shaders/effects/color_grade.comp")),
+    (IDENTITY_LUT, include_str!("shaders/effects/identity_lut.comp")),
+];
+
+pub fn is_builtin(path: &str) -> bool {
+    path.starts_with(BUILTIN_PREFIX)
+}
+
+/*
+ * Source of the built-in shader at @path, None when it's a file or no such shader exists
+ */
+pub fn builtin_source(path: &str) -> Option<&'static str> {
+    SOURCES.iter().find(|(p, _)| *p == path).map(|(_, source)| *source)
+}
+
+/*
+ * Whether the built-in shader at @path works on values above 1, which 8-bit images clamp away
+ */
+pub fn needs_float_images(path: &str) -> bool {
+    path == BLOOM_THRESHOLD || path == TONEMAP
+}
+
+/**
+ *  Curve mapping exposed linear color to the displayable range.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tonemapper {
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+/*
+ * Separable gaussian blur of @input with standard deviation @sigma in pixels. The horizontal pass writes to
+ * @scratch, the vertical pass reads it and writes to @output. The passes are named @name followed by their direction.
+ */
+pub fn gaussian_blur(name: &str, input: impl Into<ImageRef>, scratch: impl Into<ImageRef>, output: impl Into<ImageRef>, sigma: f32) -> [Pass; 2] {
+    let scratch = scratch.into();
+    [
+        blur_pass(input.into(), scratch.clone(), 0, sigma).name(&format!("{} horizontal", name)),
+        blur_pass(scratch, output.into(), 1, sigma).name(&format!("{} vertical", name)),
+    ]
+}
+
+fn blur_pass(input: ImageRef, output: ImageRef, direction: u32, sigma: f32) -> Pass {
+    Pass::fullscreen(GAUSSIAN_BLUR, [input], [output])
+        .define("DIRECTION", direction)
+        .specialize(SpecConstant::new(0, "radius", SpecValue::Int((sigma * 3.0).ceil().max(1.0) as i32)))
+        .specialize(SpecConstant::new(1, "sigma", SpecValue::Float(sigma)))
+}
+
+/*
+ * Glow around the parts of @input brighter than @threshold, blurred with @sigma in pixels and added to @output with
+ * @intensity. @bright and @scratch hold the bright parts while they're blurred. The passes are named @name followed
+ * by their step. Needs float images, 8-bit images have nothing above 1 to bloom.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn bloom(name: &str, input: impl Into<ImageRef>, bright: impl Into<ImageRef>, scratch: impl Into<ImageRef>, output: impl Into<ImageRef>, threshold: f32, sigma: f32, intensity: f32) -> [Pass; 4] {
+    let input = input.into();
+    let bright = bright.into();
+    let scratch = scratch.into();
+    [
+        Pass::fullscreen(BLOOM_THRESHOLD, [input.clone()], [bright.clone()])
+            .name(&format!("{} threshold", name))
+            .specialize(SpecConstant::new(0, "threshold", SpecValue::Float(threshold)))
+            .specialize(SpecConstant::new(1, "knee", SpecValue::Float(0.1))),
+        blur_pass(bright.clone(), scratch.clone(), 0, sigma).name(&format!("{} blur horizontal", name)),
+        blur_pass(scratch, bright.clone(), 1, sigma).name(&format!("{} blur vertical", name)),
+        Pass::fullscreen(BLOOM_COMPOSITE, [input, bright], [output.into()])
+            .name(&format!("{} composite", name))
+            .specialize(SpecConstant::new(0, "intensity", SpecValue::Float(intensity))),
+    ]
+}
+
+/*
+ * Tonemap the linear color of @input, scaled by @exposure, with @tonemapper into [0, 1]. Needs float images, 8-bit
+ * images are already clamped to that range.
+ */
+pub fn tonemap(name: &str, input: impl Into<ImageRef>, output: impl Into<ImageRef>, tonemapper: Tonemapper, exposure: f32) -> Pass {
+    let operator = match tonemapper {
+        Tonemapper::Reinhard => 0,
+        Tonemapper::Aces => 1,
+    };
+    Pass::fullscreen(TONEMAP, [input.into()], [output.into()])
+        .name(name)
+        .specialize(SpecConstant::new(0, "operator", SpecValue::Int(operator)))
+        .specialize(SpecConstant::new(1, "exposure", SpecValue::Float(exposure)))
+}
+
+pub fn fxaa(name: &str, input: impl Into<ImageRef>, output: impl Into<ImageRef>) -> Pass {
+    Pass::fullscreen(FXAA, [input.into()], [output.into()])
+        .name(name)
+        .specialize(SpecConstant::new(0, "span max", SpecValue::Float(8.0)))
+        .specialize(SpecConstant::new(1, "reduce mul", SpecValue::Float(1.0 / 8.0)))
+        .specialize(SpecConstant::new(2, "reduce min", SpecValue::Float(1.0 / 128.0)))
+}
+
+/*
+ * Shift the red and blue channels of @input apart by up to @strength pixels in the corners
+ */
+pub fn chromatic_aberration(name: &str, input: impl Into<ImageRef>, output: impl Into<ImageRef>, strength: f32) -> Pass {
+    Pass::fullscreen(CHROMATIC_ABERRATION, [input.into()], [output.into()])
+        .name(name)
+        .specialize(SpecConstant::new(0, "strength", SpecValue::Float(strength)))
+}
+
+/*
+ * Add grain with amplitude @intensity to @input, changing 24 times per second of the clock
+ */
+pub fn film_grain(name: &str, input: impl Into<ImageRef>, output: impl Into<ImageRef>, intensity: f32) -> Pass {
+    Pass::fullscreen(FILM_GRAIN, [input.into()], [output.into()])
+        .name(name)
+        .specialize(SpecConstant::new(0, "intensity", SpecValue::Float(intensity)))
+        .specialize(SpecConstant::new(1, "fps", SpecValue::Float(24.0)))
+}
+
+/*
+ * Grade @input with the @lut_size³ lookup table in @lut, blended with the original by @intensity. The table is a strip
+ * of @lut_size slices in the top left of @lut, see identity_lut for the layout.
+ */
+pub fn color_grade(name: &str, input: impl Into<ImageRef>, lut: impl Into<ImageRef>, output: impl Into<ImageRef>, lut_size: u32, intensity: f32) -> Pass {
+    Pass::fullscreen(COLOR_GRADE, [input.into(), lut.into()], [output.into()])
+        .name(name)
+        .specialize(SpecConstant::new(0, "lut size", SpecValue::Int(lut_size.max(2) as i32)))
+        .specialize(SpecConstant::new(1, "intensity", SpecValue::Float(intensity)))
+}
+
+/*
+ * Write a @lut_size³ lookup table that keeps colors unchanged to @output, as a starting point for grading passes.
+ * Slice b is at x = b * @lut_size, within a slice red increases to the right and green downwards.
+ */
+pub fn identity_lut(name: &str, output: impl Into<ImageRef>, lut_size: u32) -> Pass {
+    Pass::new(IDENTITY_LUT)
+        .output(output)
+        .name(name)
+        .specialize(SpecConstant::new(0, "lut size", SpecValue::Int(lut_size.max(2) as i32)))
+}
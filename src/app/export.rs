@@ -0,0 +1,445 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageBuffer, ImageFormat};
+use crate::app::readback::{encode_display, to_u16, to_u8, Tonemap};
+use crate::Error;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ExportFormat {
+    Png8,
+    Png16,
+    Exr,
+    Hdr,
+    Jpeg,
+    WebP,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 6] = [ExportFormat::Png8, ExportFormat::Png16, ExportFormat::Exr, ExportFormat::Hdr, ExportFormat::Jpeg, ExportFormat::WebP];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Png8 => "PNG 8-bit",
+            ExportFormat::Png16 => "PNG 16-bit",
+            ExportFormat::Exr => "EXR float",
+            ExportFormat::Hdr => "HDR float",
+            ExportFormat::Jpeg => "JPEG",
+            ExportFormat::WebP => "WebP",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png8 | ExportFormat::Png16 => "png",
+            ExportFormat::Exr => "exr",
+            ExportFormat::Hdr => "hdr",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::WebP => "webp",
+        }
+    }
+
+    /*
+     * Float formats store linear values, the others display values
+     */
+    pub fn is_float(&self) -> bool {
+        matches!(self, ExportFormat::Exr | ExportFormat::Hdr)
+    }
+
+    /*
+     * Formats with more than 8 bits per channel, exporting them from 8-bit images would only add empty bits
+     */
+    pub fn needs_float_images(&self) -> bool {
+        matches!(self, ExportFormat::Png16 | ExportFormat::Exr | ExportFormat::Hdr)
+    }
+
+    pub fn supports_alpha(&self) -> bool {
+        !matches!(self, ExportFormat::Hdr | ExportFormat::Jpeg)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DownsampleFilter {
+    /// Average of the samples within each output pixel
+    Box,
+    /// Tent weighted average reaching into the neighbouring pixels, softer but with less aliasing
+    Triangle,
+}
+
+/*
+ * Reduce an RGBA image of @width x @height by @factor in both dimensions
+ */
+pub fn downsample(data: &[f32], width: u32, height: u32, factor: u32, filter: DownsampleFilter) -> Vec<f32> {
+    if factor <= 1 {
+        return data.to_vec();
+    }
+
+    let (out_width, out_height) = (width / factor, height / factor);
+    let radius = match filter {
+        DownsampleFilter::Box => factor as f32 * 0.5,
+        DownsampleFilter::Triangle => factor as f32,
+    };
+    let weight = |d: f32| match filter {
+        DownsampleFilter::Box => if d.abs() <= radius { 1.0 } else { 0.0 },
+        DownsampleFilter::Triangle => (1.0 - d.abs() / radius).max(0.0),
+    };
+
+    let mut output = vec![0.0f32; (out_width * out_height * 4) as usize];
+    for oy in 0..out_height {
+        for ox in 0..out_width {
+            // Center of the output pixel in input coordinates
+            let cx = (ox as f32 + 0.5) * factor as f32;
+            let cy = (oy as f32 + 0.5) * factor as f32;
+            let x0 = (cx - radius).floor().max(0.0) as u32;
+            let x1 = ((cx + radius).ceil() as u32).min(width);
+            let y0 = (cy - radius).floor().max(0.0) as u32;
+            let y1 = ((cy + radius).ceil() as u32).min(height);
+
+            let mut sum = [0.0f32; 4];
+            let mut total = 0.0;
+            for y in y0..y1 {
+                let wy = weight(y as f32 + 0.5 - cy);
+                for x in x0..x1 {
+                    let w = wy * weight(x as f32 + 0.5 - cx);
+                    if w == 0.0 {
+                        continue;
+                    }
+                    let i = ((y * width + x) * 4) as usize;
+                    sum.iter_mut().zip(&data[i..i + 4]).for_each(|(s, v)| *s += v * w);
+                    total += w;
+                }
+            }
+
+            let o = ((oy * out_width + ox) * 4) as usize;
+            output[o..o + 4].iter_mut().zip(sum).for_each(|(o, s)| *o = s / total);
+        }
+    }
+
+    output
+}
+
+/*
+ * Write linear RGBA pixels to @path in @format, alpha is dropped when @alpha is off or unsupported.
+ * Integer formats are tonemapped with @tonemap and sRGB encoded.
+ */
+pub fn write_image(mut data: Vec<f32>, width: u32, height: u32, format: ExportFormat, alpha: bool, tonemap: Tonemap, path: &str) -> Result<(), Error> {
+    if !format.is_float() {
+        encode_display(&mut data, tonemap);
+    }
+    let rgba = ImageBuffer::from_raw(width, height, data)
+        .ok_or(Error::Encode("Image data doesn't match its size".to_string()))?;
+    let image = DynamicImage::ImageRgba32F(rgba);
+    let alpha = alpha && format.supports_alpha();
+
+    let image = match (format, alpha) {
+        (ExportFormat::Png8 | ExportFormat::Jpeg | ExportFormat::WebP, true) => DynamicImage::ImageRgba8(image.to_rgba8()),
+        (ExportFormat::Png8 | ExportFormat::Jpeg | ExportFormat::WebP, false) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        (ExportFormat::Png16, true) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        (ExportFormat::Png16, false) => DynamicImage::ImageRgb16(image.to_rgb16()),
+        (ExportFormat::Exr | ExportFormat::Hdr, true) => image,
+        (ExportFormat::Exr | ExportFormat::Hdr, false) => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+    };
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        ExportFormat::Png8 | ExportFormat::Png16 => image.write_to(&mut writer, ImageFormat::Png)?,
+        ExportFormat::Exr => image.write_to(&mut writer, ImageFormat::OpenExr)?,
+        ExportFormat::Hdr => image.write_to(&mut writer, ImageFormat::Hdr)?,
+        ExportFormat::Jpeg => image.write_with_encoder(JpegEncoder::new_with_quality(&mut writer, 95))?,
+        ExportFormat::WebP => image.write_to(&mut writer, ImageFormat::WebP)?,
+    }
+
+    Ok(())
+}
+
+/// Largest image in pixels a RowWriter collects in memory for formats it can't stream, 1 GiB of RGBA floats
+pub const MAX_BUFFERED_PIXELS: u64 = 8192 * 8192;
+
+/**
+ *  Writes an image in horizontal bands, so images that don't fit in memory as a whole can be exported.
+ *  PNG and HDR rows are streamed to disk as they come in, other formats are collected and written on finish, up to
+ *  MAX_BUFFERED_PIXELS.
+ */
+pub struct RowWriter {
+    width: u32,
+    height: u32,
+    format: ExportFormat,
+    alpha: bool,
+    tonemap: Tonemap,
+    path: String,
+    rows: u32,
+    target: RowTarget,
+}
+
+enum RowTarget {
+    Png(Box<png::StreamWriter<'static, BufWriter<File>>>),
+    Hdr(BufWriter<File>),
+    Buffer(Vec<f32>),
+}
+
+impl RowWriter {
+    pub fn new(width: u32, height: u32, format: ExportFormat, alpha: bool, tonemap: Tonemap, path: &str) -> Result<RowWriter, Error> {
+        let alpha = alpha && format.supports_alpha();
+        let streamed = matches!(format, ExportFormat::Png8 | ExportFormat::Png16 | ExportFormat::Hdr);
+        if !streamed && width as u64 * height as u64 > MAX_BUFFERED_PIXELS {
+            return Err(Error::Encode(format!(
+                "A {}x{} image is too large to export as {}, only PNG and HDR are written in parts. Export as one of those or at most {} pixels.",
+                width, height, format.name(), MAX_BUFFERED_PIXELS
+            )));
+        }
+
+        let target = match format {
+            ExportFormat::Png8 | ExportFormat::Png16 => {
+                let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+                encoder.set_color(if alpha { png::ColorType::Rgba } else { png::ColorType::Rgb });
+                encoder.set_depth(if format == ExportFormat::Png16 { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+                RowTarget::Png(Box::new(encoder.write_header()?.into_stream_writer()?))
+            },
+            ExportFormat::Hdr => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                writer.write_all(format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).as_bytes())?;
+                RowTarget::Hdr(writer)
+            },
+            _ => RowTarget::Buffer(Vec::with_capacity((width * height * 4) as usize)),
+        };
+
+        Ok(Self {
+            width,
+            height,
+            format,
+            alpha,
+            tonemap,
+            path: path.to_string(),
+            rows: 0,
+            target,
+        })
+    }
+
+    /*
+     * Append full rows of linear RGBA pixels below the ones written so far
+     */
+    pub fn write_rows(&mut self, data: &[f32]) -> Result<(), Error> {
+        let rows = (data.len() / (self.width * 4) as usize) as u32;
+        if self.rows + rows > self.height {
+            return Err(Error::Encode(format!("Writing row {} of an image with {} rows", self.rows + rows, self.height)));
+        }
+        self.rows += rows;
+
+        match &mut self.target {
+            RowTarget::Png(writer) => {
+                let mut data = data.to_vec();
+                encode_display(&mut data, self.tonemap);
+                let channels = if self.alpha { 4 } else { 3 };
+                let samples = data.chunks_exact(4).flat_map(|p| &p[..channels]);
+                let bytes = match self.format {
+                    ExportFormat::Png16 => samples.flat_map(|&v| to_u16(v).to_be_bytes()).collect::<Vec<u8>>(),
+                    _ => samples.map(|&v| to_u8(v)).collect::<Vec<u8>>(),
+                };
+                writer.write_all(&bytes)?;
+            },
+            RowTarget::Hdr(writer) => {
+                for row in data.chunks_exact((self.width * 4) as usize) {
+                    writer.write_all(&hdr_scanline(row))?;
+                }
+            },
+            RowTarget::Buffer(buffer) => buffer.extend_from_slice(data),
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        if self.rows != self.height {
+            return Err(Error::Encode(format!("Image has {} of {} rows", self.rows, self.height)));
+        }
+
+        match self.target {
+            RowTarget::Png(writer) => Ok(writer.finish()?),
+            RowTarget::Hdr(mut writer) => Ok(writer.flush()?),
+            RowTarget::Buffer(buffer) => write_image(buffer, self.width, self.height, self.format, self.alpha, self.tonemap, &self.path),
+        }
+    }
+}
+
+/*
+ * Shared exponent encoding of a linear RGB color, negative and NaN channels become 0
+ */
+fn to_rgbe(color: &[f32]) -> [u8; 4] {
+    let [r, g, b] = [color[0], color[1], color[2]].map(|c| if c > 0.0 { c } else { 0.0 });
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+    // Scale the largest channel into [128, 256)
+    let exponent = (max.log2().floor() as i32 + 1).clamp(-128, 127);
+    let scale = 256.0 / 2f32.powi(exponent);
+    let channel = |c: f32| (c * scale).min(255.0) as u8;
+    [channel(r), channel(g), channel(b), (exponent + 128) as u8]
+}
+
+/*
+ * A Radiance HDR scanline of an RGBA @row. Widths the run length encoding supports are written in that layout,
+ * with each channel in uncompressed chunks, others as flat RGBE pixels.
+ */
+fn hdr_scanline(row: &[f32]) -> Vec<u8> {
+    let pixels = row.chunks_exact(4).map(to_rgbe).collect::<Vec<[u8; 4]>>();
+    let width = pixels.len();
+    if !(8..0x8000).contains(&width) {
+        return pixels.concat();
+    }
+
+    let mut bytes = vec![2, 2, (width >> 8) as u8, (width & 0xff) as u8];
+    for channel in 0..4 {
+        for chunk in pixels.chunks(128) {
+            bytes.push(chunk.len() as u8);
+            bytes.extend(chunk.iter().map(|p| p[channel]));
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageReader;
+
+    fn gradient(width: u32, height: u32) -> Vec<f32> {
+        (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = ((i % width) as f32, (i / width) as f32);
+                [x * 0.37, y * 2.5, (x + y) * 0.01, 1.0]
+            })
+            .collect()
+    }
+
+    /*
+     * RGBA image of @width x @height with x + 10 * y in red, twice that in green and opaque alpha
+     */
+    fn ramp(width: u32, height: u32) -> Vec<f32> {
+        (0..width * height)
+            .flat_map(|i| {
+                let v = (i % width) as f32 + 10.0 * (i / width) as f32;
+                [v, 2.0 * v, 0.0, 1.0]
+            })
+            .collect()
+    }
+
+    fn assert_pixels(actual: &[f32], expected: &[[f32; 4]]) {
+        assert_eq!(actual.len(), expected.len() * 4);
+        for (a, e) in actual.chunks(4).zip(expected) {
+            assert!(a.iter().zip(e).all(|(a, e)| (a - e).abs() < 1e-5), "{:?} != {:?}", a, e);
+        }
+    }
+
+    #[test]
+    fn box_filter_averages_blocks() {
+        let output = downsample(&ramp(4, 2), 4, 2, 2, DownsampleFilter::Box);
+        // (0 + 1 + 10 + 11) / 4 and (2 + 3 + 12 + 13) / 4
+        assert_pixels(&output, &[[5.5, 11.0, 0.0, 1.0], [7.5, 15.0, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn triangle_filter_weights_neighbours() {
+        let output = downsample(&ramp(4, 2), 4, 2, 2, DownsampleFilter::Triangle);
+        // Weights 0.75, 0.75 and 0.25 around the centers at x = 1 and 3, the sample past the edge is dropped:
+        // (0.75 * 0 + 0.75 * 1 + 0.25 * 2) / 1.75 and (0.25 * 1 + 0.75 * 2 + 0.75 * 3) / 1.75, the rows average to 5
+        let left = 1.25 / 1.75 + 5.0;
+        let right = 4.0 / 1.75 + 5.0;
+        assert_pixels(&output, &[[left, 2.0 * left, 0.0, 1.0], [right, 2.0 * right, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn factor_one_is_identity() {
+        let data = ramp(3, 2);
+        for filter in [DownsampleFilter::Box, DownsampleFilter::Triangle] {
+            assert_eq!(downsample(&data, 3, 2, 1, filter), data);
+        }
+    }
+
+    #[test]
+    fn constant_image_stays_constant() {
+        let data = [0.25, 0.5, 0.75, 1.0].repeat(6 * 6);
+        for filter in [DownsampleFilter::Box, DownsampleFilter::Triangle] {
+            assert_pixels(&downsample(&data, 6, 6, 3, filter), &[[0.25, 0.5, 0.75, 1.0]; 4]);
+        }
+    }
+
+    /*
+     * Stream @data in bands of @band rows to a temporary file named @name, and read it back as linear RGB floats
+     */
+    fn stream(data: &[f32], width: u32, height: u32, band: u32, format: ExportFormat, name: &str) -> Vec<f32> {
+        let path = std::env::temp_dir().join(format!("kiyo_{}_{}", std::process::id(), name));
+        let path = path.to_str().unwrap();
+        let mut writer = RowWriter::new(width, height, format, false, Tonemap::Clamp, path).unwrap();
+        for rows in data.chunks((width * band * 4) as usize) {
+            writer.write_rows(rows).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let image = ImageReader::open(path).unwrap().decode().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!((image.width(), image.height()), (width, height));
+        image.to_rgb32f().into_raw()
+    }
+
+    fn assert_hdr_close(data: &[f32], decoded: &[f32]) {
+        for (i, (expected, decoded)) in data.chunks_exact(4).zip(decoded.chunks_exact(3)).enumerate() {
+            // RGBE keeps 8 bits of mantissa relative to the brightest channel of a pixel
+            let tolerance = expected[..3].iter().fold(0.0f32, |m, &c| m.max(c)) / 128.0;
+            for (&e, &d) in expected.iter().zip(decoded) {
+                assert!((e - d).abs() <= tolerance, "pixel {}: {:?} != {:?}", i, &expected[..3], decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn hdr_rows_are_streamed() {
+        let (width, height) = (300, 7);
+        let data = gradient(width, height);
+        assert_hdr_close(&data, &stream(&data, width, height, 3, ExportFormat::Hdr, "rle.hdr"));
+    }
+
+    #[test]
+    fn narrow_hdr_images_are_flat() {
+        let (width, height) = (5, 4);
+        let data = gradient(width, height);
+        assert_hdr_close(&data, &stream(&data, width, height, 1, ExportFormat::Hdr, "flat.hdr"));
+    }
+
+    #[test]
+    fn rgbe_encoding() {
+        assert_eq!(to_rgbe(&[0.0, 0.0, 0.0]), [0; 4]);
+        assert_eq!(to_rgbe(&[-1.0, f32::NAN, 0.0]), [0; 4]);
+        assert_eq!(to_rgbe(&[1.0, 0.5, 0.0]), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(&[0.0, 0.0, 3.0]), [0, 0, 192, 130]);
+    }
+
+    #[test]
+    fn png_rows_are_streamed() {
+        let (width, height) = (16, 9);
+        let data = gradient(width, height).iter().map(|v| v.fract()).collect::<Vec<f32>>();
+        let decoded = stream(&data, width, height, 2, ExportFormat::Png16, "rows.png");
+        let mut expected = data.clone();
+        encode_display(&mut expected, Tonemap::Clamp);
+        let expected = expected.chunks_exact(4).flat_map(|p| &p[..3]);
+        assert!(expected.zip(decoded).all(|(e, d)| (e - d).abs() < 1e-4));
+    }
+
+    #[test]
+    fn large_buffered_formats_are_rejected() {
+        let path = std::env::temp_dir().join(format!("kiyo_{}_large.exr", std::process::id()));
+        let result = RowWriter::new(10000, 10000, ExportFormat::Exr, false, Tonemap::Clamp, path.to_str().unwrap());
+        assert!(matches!(result, Err(Error::Encode(_))));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rows_must_fill_the_image() {
+        let path = std::env::temp_dir().join(format!("kiyo_{}_short.exr", std::process::id()));
+        let mut writer = RowWriter::new(4, 4, ExportFormat::Exr, false, Tonemap::Clamp, path.to_str().unwrap()).unwrap();
+        writer.write_rows(&gradient(4, 2)).unwrap();
+        assert!(writer.write_rows(&gradient(4, 3)).is_err());
+        assert!(writer.finish().is_err());
+        assert!(!path.exists());
+    }
+}
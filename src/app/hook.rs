@@ -0,0 +1,213 @@
+use log::warn;
+use crate::app::input::{Action, InputState};
+
+/**
+ *  State of the frame about to be rendered, as seen by a FrameHook. Changes apply to this frame.
+ */
+pub struct FrameContext<'a> {
+    time: f32,
+    delta: f32,
+    frame: u64,
+    input: &'a InputState,
+    /// Animated parameters by name, with their value this frame
+    parameters: Vec<(String, f32)>,
+    /// Passes by name, with whether they run
+    passes: Vec<(String, bool)>,
+    /// Uploaded data, following the input state in the parameter buffer until replaced
+    data: &'a mut Vec<f32>,
+    actions: Vec<Action>,
+}
+
+impl<'a> FrameContext<'a> {
+    pub fn new(time: f32, delta: f32, frame: u64, input: &'a InputState, parameters: Vec<(String, f32)>, passes: Vec<(String, bool)>, data: &'a mut Vec<f32>) -> FrameContext<'a> {
+        Self {
+            time,
+            delta,
+            frame,
+            input,
+            parameters,
+            passes,
+            data,
+            actions: Vec::new(),
+        }
+    }
+
+    /*
+     * Time of the clock in seconds, as passed to the shaders
+     */
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /*
+     * Clock time since the previous frame, zero while paused and negative after seeking back
+     */
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn input(&self) -> &InputState {
+        self.input
+    }
+
+    /*
+     * Value of the animated parameter @name this frame
+     */
+    pub fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+
+    /*
+     * Override the value of the animated parameter @name for this frame
+     */
+    pub fn set_parameter(&mut self, name: &str, value: f32) {
+        match self.parameters.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => warn!("Frame hook sets parameter \"{}\", which doesn't exist", name),
+        }
+    }
+
+    /*
+     * Whether the pass @name runs, None when there's no such pass
+     */
+    pub fn pass_enabled(&self, name: &str) -> Option<bool> {
+        self.passes.iter().find(|(n, _)| n == name).map(|(_, e)| *e)
+    }
+
+    pub fn set_pass_enabled(&mut self, name: &str, enabled: bool) {
+        match self.passes.iter_mut().find(|(n, _)| n == name) {
+            Some((_, e)) => *e = enabled,
+            None => warn!("Frame hook sets pass \"{}\", which doesn't exist", name),
+        }
+    }
+
+    /*
+     * Data uploaded so far, readable in shaders as `params[ USER_DATA + i ]`
+     */
+    pub fn data(&self) -> &[f32] {
+        self.data
+    }
+
+    /*
+     * Replace the uploaded data with @data, it's kept for the following frames
+     */
+    pub fn upload(&mut self, data: &[f32]) {
+        self.data.clear();
+        self.data.extend_from_slice(data);
+    }
+
+    /*
+     * Perform @action after this frame is set up, like a key binding would
+     */
+    pub fn perform(&mut self, action: Action) {
+        self.actions.push(action);
+    }
+
+    /*
+     * The parameters, passes and actions as changed by the hook
+     */
+    pub fn finish(self) -> FrameChanges {
+        FrameChanges {
+            parameters: self.parameters,
+            passes: self.passes,
+            actions: self.actions,
+        }
+    }
+}
+
+/**
+ *  What a FrameHook changed in a frame.
+ */
+pub struct FrameChanges {
+    pub parameters: Vec<(String, f32)>,
+    pub passes: Vec<(String, bool)>,
+    pub actions: Vec<Action>,
+}
+
+/**
+ *  Custom logic run before every frame, to drive simulations from Rust. Exports don't run it, they use the data it
+ *  last uploaded and the animated parameters without its overrides.
+ */
+pub trait FrameHook {
+    fn update(&mut self, context: &mut FrameContext);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(input: &'a InputState, data: &'a mut Vec<f32>) -> FrameContext<'a> {
+        let parameters = Vec::from([("speed".to_string(), 1.0), ("size".to_string(), 2.0)]);
+        let passes = Vec::from([("scene".to_string(), true), ("blur".to_string(), false)]);
+        FrameContext::new(2.0, 0.5, 7, input, parameters, passes, data)
+    }
+
+    #[test]
+    fn clock_and_input_are_passed_through() {
+        let mut input = InputState::new();
+        input.key(b'A', true);
+        let mut data = Vec::new();
+        let context = context(&input, &mut data);
+        assert_eq!((context.time(), context.delta(), context.frame()), (2.0, 0.5, 7));
+        assert!(context.input().key_down(b'A'));
+    }
+
+    #[test]
+    fn parameters_are_overridden_in_order() {
+        let input = InputState::new();
+        let mut data = Vec::new();
+        let mut context = context(&input, &mut data);
+        assert_eq!(context.parameter("size"), Some(2.0));
+        assert_eq!(context.parameter("missing"), None);
+
+        context.set_parameter("size", 5.0);
+        context.set_parameter("missing", 3.0);
+        assert_eq!(context.parameter("size"), Some(5.0));
+        assert_eq!(context.finish().parameters, Vec::from([("speed".to_string(), 1.0), ("size".to_string(), 5.0)]));
+    }
+
+    #[test]
+    fn passes_are_toggled() {
+        let input = InputState::new();
+        let mut data = Vec::new();
+        let mut context = context(&input, &mut data);
+        assert_eq!(context.pass_enabled("blur"), Some(false));
+        assert_eq!(context.pass_enabled("missing"), None);
+
+        context.set_pass_enabled("blur", true);
+        context.set_pass_enabled("scene", false);
+        context.set_pass_enabled("missing", true);
+        assert_eq!(context.finish().passes, Vec::from([("scene".to_string(), false), ("blur".to_string(), true)]));
+    }
+
+    #[test]
+    fn uploads_replace_the_data_and_persist() {
+        let input = InputState::new();
+        let mut data = Vec::from([9.0; 8]);
+        {
+            let mut context = context(&input, &mut data);
+            assert_eq!(context.data(), &[9.0; 8]);
+            context.upload(&[1.0, 2.0]);
+            assert_eq!(context.data(), &[1.0, 2.0]);
+        }
+        assert_eq!(data, Vec::from([1.0, 2.0]));
+
+        // A frame without upload keeps the data
+        context(&input, &mut data).finish();
+        assert_eq!(data, Vec::from([1.0, 2.0]));
+    }
+
+    #[test]
+    fn actions_are_collected() {
+        let input = InputState::new();
+        let mut data = Vec::new();
+        let mut context = context(&input, &mut data);
+        context.perform(Action::Seek(1.0));
+        context.perform(Action::TogglePass("blur".to_string()));
+        assert_eq!(context.finish().actions, Vec::from([Action::Seek(1.0), Action::TogglePass("blur".to_string())]));
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::{Arc, Mutex};
+use ash::vk;
+use cen::vulkan::{Allocator, CommandBuffer, Device, Pipeline};
+use gpu_allocator::MemoryLocation;
+use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme};
+use log::error;
+use crate::Error;
+
+/**
+ *  Format of the images of a DrawConfig, all its images share it. Images hold linear color, the window shows them
+ *  sRGB encoded with values outside [0, 1] clamped.
+ */
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub enum ImageFormat {
+    /// 8 bits per channel, values are clamped to [0, 1] when written
+    #[default]
+    Rgba8,
+    /// Half floats, for HDR color and data outside [0, 1]
+    Rgba16Float,
+    Rgba32Float,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 3] = [ImageFormat::Rgba8, ImageFormat::Rgba16Float, ImageFormat::Rgba32Float];
+
+    pub fn vk(&self) -> vk::Format {
+        match self {
+            ImageFormat::Rgba8 => vk::Format::R8G8B8A8_UNORM,
+            ImageFormat::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+            ImageFormat::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
+        }
+    }
+
+    /*
+     * Format layout qualifier in GLSL, provided to shaders as IMAGE_FORMAT
+     */
+    pub fn glsl(&self) -> &'static str {
+        match self {
+            ImageFormat::Rgba8 => "rgba8",
+            ImageFormat::Rgba16Float => "rgba16f",
+            ImageFormat::Rgba32Float => "rgba32f",
+        }
+    }
+
+    /*
+     * Texel format of storage textures in WGSL
+     */
+    pub fn wgsl(&self) -> &'static str {
+        match self {
+            ImageFormat::Rgba8 => "rgba8unorm",
+            ImageFormat::Rgba16Float => "rgba16float",
+            ImageFormat::Rgba32Float => "rgba32float",
+        }
+    }
+
+    /*
+     * Float formats keep values outside [0, 1], NaN and infinity
+     */
+    pub fn is_float(&self) -> bool {
+        !matches!(self, ImageFormat::Rgba8)
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            ImageFormat::Rgba8 => 4,
+            ImageFormat::Rgba16Float => 8,
+            ImageFormat::Rgba32Float => 16,
+        }
+    }
+}
+
+/**
+ *  2D image with a view for use as storage image. cen creates all its images as R8G8B8A8_UNORM, these can have any
+ *  ImageFormat.
+ */
+pub struct StorageImage {
+    device: Device,
+    allocator: Arc<Mutex<gpu_allocator::vulkan::Allocator>>,
+    image: vk::Image,
+    view: vk::ImageView,
+    allocation: Option<Allocation>,
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+}
+
+impl StorageImage {
+    /*
+     * Image of @width by @height in @format, in the UNDEFINED layout. Besides storage it can be used as @usage.
+     */
+    pub fn new(device: &Device, allocator: &Allocator, width: u32, height: u32, format: ImageFormat, usage: vk::ImageUsageFlags) -> Result<StorageImage, Error> {
+        let create_info = vk::ImageCreateInfo::default()
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .usage(usage | vk::ImageUsageFlags::STORAGE)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .array_layers(1)
+            .mip_levels(1)
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format.vk());
+        let image = unsafe { device.handle().create_image(&create_info, None) }
+            .map_err(|e| Error::Gpu(format!("Failed to create a {}x{} {:?} image: {}", width, height, format, e)))?;
+
+        let allocator = allocator.handle();
+        let requirements = unsafe { device.handle().get_image_memory_requirements(image) };
+        let allocation = allocator.lock().unwrap()
+            .allocate(&AllocationCreateDesc {
+                name: "Storage image",
+                requirements,
+                location: MemoryLocation::GpuOnly,
+                linear: false,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            });
+        let allocation = match allocation {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.handle().destroy_image(image, None) };
+                return Err(Error::Gpu(format!("Failed to allocate a {}x{} {:?} image: {}", width, height, format, e)));
+            },
+        };
+
+        // The image owns its allocation from here on, dropping it cleans up after the failures below
+        let mut storage_image = Self {
+            device: device.clone(),
+            allocator,
+            image,
+            view: vk::ImageView::null(),
+            allocation: Some(allocation),
+            width,
+            height,
+            format,
+        };
+        let allocation = storage_image.allocation.as_ref().unwrap();
+        unsafe { device.handle().bind_image_memory(image, allocation.memory(), allocation.offset()) }
+            .map_err(|e| Error::Gpu(format!("Failed to bind image memory: {}", e)))?;
+
+        let view_create_info = vk::ImageViewCreateInfo::default()
+            .format(format.vk())
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        storage_image.view = unsafe { device.handle().create_image_view(&view_create_info, None) }
+            .map_err(|e| Error::Gpu(format!("Failed to create image view: {}", e)))?;
+
+        Ok(storage_image)
+    }
+
+    pub fn handle(&self) -> &vk::Image {
+        &self.image
+    }
+
+    /*
+     * Descriptor of the image in the GENERAL layout, as passes use it
+     */
+    pub fn binding(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::default()
+            .image_layout(vk::ImageLayout::GENERAL)
+            .image_view(self.view)
+    }
+
+    /*
+     * Bytes of the pixels of the image
+     */
+    pub fn memory(&self) -> u64 {
+        self.width as u64 * self.height as u64 * self.format.bytes_per_pixel() as u64
+    }
+}
+
+impl Drop for StorageImage {
+    fn drop(&mut self) {
+        unsafe {
+            if self.view != vk::ImageView::null() {
+                self.device.handle().destroy_image_view(self.view, None);
+            }
+            self.device.handle().destroy_image(self.image, None);
+        }
+        if let Some(allocation) = self.allocation.take() {
+            if let Err(e) = self.allocator.lock().unwrap().free(allocation) {
+                error!("Failed to free image memory: {}", e);
+            }
+        }
+    }
+}
+
+/*
+ * Bind @images as the storage image array at binding 0 of @pipeline
+ */
+pub fn bind_images(command_buffer: &CommandBuffer, pipeline: &dyn Pipeline, images: &[&StorageImage]) {
+    let bindings = images.iter().map(|i| i.binding()).collect::<Vec<vk::DescriptorImageInfo>>();
+    command_buffer.bind_push_descriptor(
+        pipeline,
+        0,
+        &[vk::WriteDescriptorSet::default()
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .image_info(&bindings)
+        ]
+    );
+}
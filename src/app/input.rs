@@ -0,0 +1,466 @@
+use egui::{Context, Event, Key};
+
+/// Keys in the keyboard state, indexed by JavaScript key code like Shadertoy's keyboard texture
+pub const KEY_COUNT: usize = 256;
+
+/// Floats of the input state in the parameter buffer
+pub const INPUT_SIZE: usize = 12 + 3 * KEY_COUNT;
+
+pub const KEY_BACKSPACE: u8 = 8;
+pub const KEY_TAB: u8 = 9;
+pub const KEY_ENTER: u8 = 13;
+pub const KEY_SHIFT: u8 = 16;
+pub const KEY_CONTROL: u8 = 17;
+pub const KEY_ALT: u8 = 18;
+pub const KEY_ESCAPE: u8 = 27;
+pub const KEY_SPACE: u8 = 32;
+pub const KEY_HOME: u8 = 36;
+pub const KEY_LEFT: u8 = 37;
+pub const KEY_UP: u8 = 38;
+pub const KEY_RIGHT: u8 = 39;
+pub const KEY_DOWN: u8 = 40;
+
+/**
+ *  Mouse and keyboard state of the current frame, fed by the window but independent of it.
+ *  Positions are in pixels of the images, with the origin in the top left.
+ */
+#[derive(Clone, Debug)]
+pub struct InputState {
+    /// Position of the pointer, None when it's outside the window or over the GUI
+    pointer: Option<[f32; 2]>,
+    button: bool,
+    /// Shadertoy's iMouse: the position while the button is held, and the position of the last click. The click x
+    /// is negative once the button is released, the click y after the frame of the click.
+    mouse: [f32; 4],
+    /// Pointer movement this frame while the button is held
+    drag: [f32; 2],
+    /// Scroll this frame and in total
+    wheel: [f32; 4],
+    /// Keys held, pressed this frame and toggled by every press
+    keys: [[bool; KEY_COUNT]; 3],
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        Self {
+            pointer: None,
+            button: false,
+            mouse: [0.0; 4],
+            drag: [0.0; 2],
+            wheel: [0.0; 4],
+            keys: [[false; KEY_COUNT]; 3],
+        }
+    }
+
+    /*
+     * Forget the events of the previous frame, called before the events of a new frame
+     */
+    pub fn begin_frame(&mut self) {
+        self.mouse[3] = -self.mouse[3].abs();
+        self.drag = [0.0; 2];
+        self.wheel[0] = 0.0;
+        self.wheel[1] = 0.0;
+        self.keys[1] = [false; KEY_COUNT];
+    }
+
+    /*
+     * Move the pointer to @position, None when it left the window
+     */
+    pub fn pointer_moved(&mut self, position: Option<[f32; 2]>) {
+        if let (true, Some(from), Some(to)) = (self.button, self.pointer, position) {
+            self.drag[0] += to[0] - from[0];
+            self.drag[1] += to[1] - from[1];
+        }
+        if let (true, Some([x, y])) = (self.button, position) {
+            self.mouse[0] = x;
+            self.mouse[1] = y;
+        }
+        self.pointer = position;
+    }
+
+    /*
+     * Press or release the mouse button, a press only counts with the pointer in the window
+     */
+    pub fn button(&mut self, down: bool) {
+        match (down, self.pointer) {
+            (true, Some([x, y])) if !self.button => {
+                self.button = true;
+                self.mouse = [x, y, x, y];
+            },
+            (false, _) if self.button => {
+                self.button = false;
+                self.mouse[2] = -self.mouse[2].abs();
+            },
+            _ => {},
+        }
+    }
+
+    pub fn scroll(&mut self, delta: [f32; 2]) {
+        self.wheel[0] += delta[0];
+        self.wheel[1] += delta[1];
+        self.wheel[2] += delta[0];
+        self.wheel[3] += delta[1];
+    }
+
+    /*
+     * Press or release the key with JavaScript key code @code, repeated presses of a held key are ignored
+     */
+    pub fn key(&mut self, code: u8, down: bool) {
+        let code = code as usize;
+        if down && !self.keys[0][code] {
+            self.keys[1][code] = true;
+            self.keys[2][code] = !self.keys[2][code];
+        }
+        self.keys[0][code] = down;
+    }
+
+    /*
+     * Release all held keys, for when the keyboard goes elsewhere
+     */
+    pub fn release_keys(&mut self) {
+        self.keys[0] = [false; KEY_COUNT];
+    }
+
+    pub fn pointer(&self) -> Option<[f32; 2]> {
+        self.pointer
+    }
+
+    pub fn mouse(&self) -> [f32; 4] {
+        self.mouse
+    }
+
+    pub fn drag(&self) -> [f32; 2] {
+        self.drag
+    }
+
+    pub fn wheel(&self) -> [f32; 4] {
+        self.wheel
+    }
+
+    pub fn key_down(&self, code: u8) -> bool {
+        self.keys[0][code as usize]
+    }
+
+    pub fn key_pressed(&self, code: u8) -> bool {
+        self.keys[1][code as usize]
+    }
+
+    pub fn key_toggled(&self, code: u8) -> bool {
+        self.keys[2][code as usize]
+    }
+
+    /*
+     * Key codes pressed this frame
+     */
+    pub fn pressed_keys(&self) -> Vec<u8> {
+        (0..KEY_COUNT).filter(|&k| self.keys[1][k]).map(|k| k as u8).collect()
+    }
+
+    /*
+     * The state as laid out in the parameter buffer after the animated parameters, see macros
+     */
+    pub fn values(&self) -> Vec<f32> {
+        let pointer = self.pointer.unwrap_or([-1.0, -1.0]);
+        let mut values = Vec::with_capacity(INPUT_SIZE);
+        values.extend(self.mouse);
+        values.extend(pointer);
+        values.extend(self.drag);
+        values.extend(self.wheel);
+        values.extend(self.keys.iter().flatten().map(|&k| if k { 1.0 } else { 0.0 }));
+        values
+    }
+}
+
+/*
+ * Shader macros with the indices of the input state in the parameter buffer, which starts at @offset
+ */
+pub fn macros(offset: usize) -> Vec<(String, String)> {
+    [("INPUT_MOUSE", 0), ("INPUT_POINTER", 4), ("INPUT_DRAG", 6), ("INPUT_WHEEL", 8), ("INPUT_KEYBOARD", 12)]
+        .iter()
+        .map(|(name, index)| (name.to_string(), (offset + index).to_string()))
+        .collect()
+}
+
+/*
+ * JavaScript key code of @key, as used by Shadertoy
+ */
+pub fn key_code(key: Key) -> Option<u8> {
+    let code = match key {
+        Key::Backspace => KEY_BACKSPACE,
+        Key::Tab => KEY_TAB,
+        Key::Enter => KEY_ENTER,
+        Key::Escape => KEY_ESCAPE,
+        Key::Space => KEY_SPACE,
+        Key::PageUp => 33,
+        Key::PageDown => 34,
+        Key::End => 35,
+        Key::Home => KEY_HOME,
+        Key::ArrowLeft => KEY_LEFT,
+        Key::ArrowUp => KEY_UP,
+        Key::ArrowRight => KEY_RIGHT,
+        Key::ArrowDown => KEY_DOWN,
+        Key::Insert => 45,
+        Key::Delete => 46,
+        Key::Semicolon => 186,
+        Key::Equals => 187,
+        Key::Comma => 188,
+        Key::Minus => 189,
+        Key::Period => 190,
+        Key::Slash => 191,
+        Key::Backtick => 192,
+        Key::OpenBracket => 219,
+        Key::Backslash => 220,
+        Key::CloseBracket => 221,
+        Key::Quote => 222,
+        key => {
+            // Digits and letters are named after their character, which is also their key code
+            let name = key.name().as_bytes();
+            if let Some(function) = key.name().strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+                return (function <= 12).then(|| 111 + function);
+            }
+            match name {
+                [c] if c.is_ascii_alphanumeric() => *c,
+                _ => return None,
+            }
+        },
+    };
+    Some(code)
+}
+
+/*
+ * Feed this frame's input of the window to @state. The pointer and mouse only count outside the GUI, the keyboard
+ * only while the GUI doesn't take text input.
+ */
+pub fn read_egui(state: &mut InputState, context: &Context) {
+    state.begin_frame();
+    let over_gui = context.is_pointer_over_area() || context.is_using_pointer();
+    let pixels_per_point = context.pixels_per_point();
+    let keyboard = !context.wants_keyboard_input();
+
+    context.input(|i| {
+        let position = i.pointer.latest_pos()
+            .filter(|_| !over_gui)
+            .map(|p| [p.x * pixels_per_point, p.y * pixels_per_point]);
+        state.pointer_moved(position);
+        if i.pointer.primary_pressed() {
+            state.button(true);
+        }
+        if !i.pointer.primary_down() {
+            state.button(false);
+        }
+        if !over_gui {
+            state.scroll([i.raw_scroll_delta.x, i.raw_scroll_delta.y]);
+        }
+
+        // Keys released while typing in the GUI would stay held
+        if !keyboard {
+            state.release_keys();
+            return;
+        }
+        state.key(KEY_SHIFT, i.modifiers.shift);
+        state.key(KEY_CONTROL, i.modifiers.ctrl);
+        state.key(KEY_ALT, i.modifiers.alt);
+        for event in &i.events {
+            if let Event::Key { key, pressed, .. } = event {
+                if let Some(code) = key_code(*key) {
+                    state.key(code, *pressed);
+                }
+            }
+        }
+    });
+}
+
+/**
+ *  What a key binding can do.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    /// Clear all images, also those without a clear config, and restart the clock
+    Reset,
+    /// Turn the pass with the given name on or off
+    TogglePass(String),
+    EnablePass(String, bool),
+    /// Play or pause the clock
+    TogglePlayback,
+    /// Jump to a time in seconds
+    Seek(f32),
+}
+
+/**
+ *  Custom key bindings, given the keys pressed while the GUI doesn't take keyboard input.
+ */
+pub trait KeyBindings {
+    /*
+     * Actions to perform for @key, a JavaScript key code like `b'R'` or KEY_SPACE, just pressed with @input
+     */
+    fn key_pressed(&mut self, key: u8, input: &InputState) -> Vec<Action>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(name: &str) -> usize {
+        let macros = macros(0);
+        macros.iter().find(|(n, _)| n == name).unwrap().1.parse().unwrap()
+    }
+
+    #[test]
+    fn keys_are_held_pressed_and_toggled() {
+        let mut state = InputState::new();
+        state.key(b'A', true);
+        assert!(state.key_down(b'A') && state.key_pressed(b'A') && state.key_toggled(b'A'));
+        assert_eq!(state.pressed_keys(), vec![b'A']);
+
+        state.begin_frame();
+        assert!(state.key_down(b'A') && !state.key_pressed(b'A') && state.key_toggled(b'A'));
+
+        state.key(b'A', false);
+        assert!(!state.key_down(b'A') && state.key_toggled(b'A'));
+
+        state.begin_frame();
+        state.key(b'A', true);
+        assert!(state.key_pressed(b'A') && !state.key_toggled(b'A'));
+    }
+
+    #[test]
+    fn key_repeats_are_ignored() {
+        let mut state = InputState::new();
+        state.key(KEY_SPACE, true);
+        state.begin_frame();
+        state.key(KEY_SPACE, true);
+        assert!(state.key_down(KEY_SPACE));
+        assert!(!state.key_pressed(KEY_SPACE));
+        assert!(state.key_toggled(KEY_SPACE));
+        assert!(state.pressed_keys().is_empty());
+    }
+
+    #[test]
+    fn released_keys_stay_toggled() {
+        let mut state = InputState::new();
+        state.key(KEY_LEFT, true);
+        state.release_keys();
+        assert!(!state.key_down(KEY_LEFT) && state.key_toggled(KEY_LEFT));
+    }
+
+    #[test]
+    fn mouse_follows_shadertoy() {
+        let mut state = InputState::new();
+        state.pointer_moved(Some([10.0, 20.0]));
+        assert_eq!(state.mouse(), [0.0; 4]);
+
+        state.button(true);
+        assert_eq!(state.mouse(), [10.0, 20.0, 10.0, 20.0]);
+
+        // The click y turns negative after the frame of the click
+        state.begin_frame();
+        state.pointer_moved(Some([15.0, 25.0]));
+        assert_eq!(state.mouse(), [15.0, 25.0, 10.0, -20.0]);
+
+        // The click x turns negative on release, the position stays where the button was released
+        state.button(false);
+        state.pointer_moved(Some([50.0, 60.0]));
+        assert_eq!(state.mouse(), [15.0, 25.0, -10.0, -20.0]);
+        assert_eq!(state.pointer(), Some([50.0, 60.0]));
+    }
+
+    #[test]
+    fn clicks_outside_the_window_are_ignored() {
+        let mut state = InputState::new();
+        state.pointer_moved(None);
+        state.button(true);
+        assert_eq!(state.mouse(), [0.0; 4]);
+        state.pointer_moved(Some([1.0, 2.0]));
+        assert_eq!(state.drag(), [0.0; 2]);
+    }
+
+    #[test]
+    fn drag_accumulates_within_a_frame() {
+        let mut state = InputState::new();
+        state.pointer_moved(Some([0.0, 0.0]));
+        state.pointer_moved(Some([5.0, 5.0]));
+        assert_eq!(state.drag(), [0.0; 2]);
+
+        state.button(true);
+        state.pointer_moved(Some([7.0, 4.0]));
+        state.pointer_moved(Some([10.0, 8.0]));
+        assert_eq!(state.drag(), [5.0, 3.0]);
+
+        state.begin_frame();
+        assert_eq!(state.drag(), [0.0; 2]);
+
+        // Leaving the window breaks the drag instead of jumping
+        state.pointer_moved(None);
+        state.pointer_moved(Some([20.0, 20.0]));
+        assert_eq!(state.drag(), [0.0; 2]);
+    }
+
+    #[test]
+    fn wheel_keeps_its_total() {
+        let mut state = InputState::new();
+        state.scroll([1.0, 2.0]);
+        state.scroll([0.5, -1.0]);
+        assert_eq!(state.wheel(), [1.5, 1.0, 1.5, 1.0]);
+        state.begin_frame();
+        assert_eq!(state.wheel(), [0.0, 0.0, 1.5, 1.0]);
+    }
+
+    #[test]
+    fn values_match_macros() {
+        let mut state = InputState::new();
+        state.pointer_moved(Some([3.0, 4.0]));
+        state.button(true);
+        state.pointer_moved(Some([5.0, 7.0]));
+        state.scroll([0.0, 2.0]);
+        state.key(b'W', true);
+        state.key(KEY_CONTROL, true);
+        state.begin_frame();
+        state.key(b'W', false);
+        state.key(KEY_CONTROL, true);
+        state.key(KEY_UP, true);
+
+        let values = state.values();
+        assert_eq!(values.len(), INPUT_SIZE);
+        assert_eq!(values[index("INPUT_MOUSE")..][..4], state.mouse());
+        assert_eq!(values[index("INPUT_POINTER")..][..2], [5.0, 7.0]);
+        assert_eq!(values[index("INPUT_DRAG")..][..2], state.drag());
+        assert_eq!(values[index("INPUT_WHEEL")..][..4], state.wheel());
+
+        let keyboard = index("INPUT_KEYBOARD");
+        let key = |row: usize, code: u8| values[keyboard + row * KEY_COUNT + code as usize];
+        assert_eq!([key(0, KEY_UP), key(1, KEY_UP), key(2, KEY_UP)], [1.0, 1.0, 1.0]);
+        assert_eq!([key(0, KEY_CONTROL), key(1, KEY_CONTROL), key(2, KEY_CONTROL)], [1.0, 0.0, 1.0]);
+        assert_eq!([key(0, b'W'), key(1, b'W'), key(2, b'W')], [0.0, 0.0, 1.0]);
+        assert_eq!(values[keyboard..].iter().sum::<f32>(), 6.0);
+    }
+
+    #[test]
+    fn macros_start_at_offset() {
+        let macros = macros(7);
+        assert!(macros.contains(&("INPUT_MOUSE".to_string(), "7".to_string())));
+        assert!(macros.contains(&("INPUT_KEYBOARD".to_string(), "19".to_string())));
+    }
+
+    #[test]
+    fn neutral_state() {
+        let values = InputState::new().values();
+        assert_eq!(values[index("INPUT_POINTER")..][..2], [-1.0, -1.0]);
+        assert!(values.iter().enumerate().all(|(i, &v)| v == 0.0 || (index("INPUT_POINTER")..index("INPUT_POINTER") + 2).contains(&i)));
+    }
+
+    #[test]
+    fn key_codes() {
+        assert_eq!(key_code(Key::A), Some(b'A'));
+        assert_eq!(key_code(Key::Num5), Some(b'5'));
+        assert_eq!(key_code(Key::Space), Some(KEY_SPACE));
+        assert_eq!(key_code(Key::F1), Some(112));
+        assert_eq!(key_code(Key::F12), Some(123));
+        assert_eq!(key_code(Key::F13), None);
+    }
+}
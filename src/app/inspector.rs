@@ -0,0 +1,261 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use egui::{pos2, vec2, Color32, Context, Grid, Rect, Rgba, Sense, Stroke, Ui};
+
+/// Width and height of the region around the hovered pixel that is read back for the magnifier, odd so the
+/// hovered pixel is in its center
+pub const PROBE_SIZE: u32 = 15;
+
+/**
+ *  Pixels of one image around the probed pixel, as RGBA floats.
+ */
+pub struct ProbeImage {
+    pub name: String,
+    pub data: Vec<f32>,
+}
+
+/**
+ *  Region of all images read back around a pixel.
+ */
+pub struct Probe {
+    pub pixel: [u32; 2],
+    /// x, y, width and height of the region in the images
+    pub region: [u32; 4],
+    pub images: Vec<ProbeImage>,
+}
+
+impl Probe {
+    /*
+     * RGBA values of @image at @pixel, None when it's outside the region
+     */
+    pub fn value(&self, image: usize, pixel: [u32; 2]) -> Option<[f32; 4]> {
+        let [x, y, width, height] = self.region;
+        if pixel[0] < x || pixel[1] < y || pixel[0] >= x + width || pixel[1] >= y + height {
+            return None;
+        }
+        let offset = (((pixel[1] - y) * width + pixel[0] - x) * 4) as usize;
+        let data = &self.images.get(image)?.data;
+        data.get(offset..offset + 4).map(|p| [p[0], p[1], p[2], p[3]])
+    }
+}
+
+/*
+ * The region of @size by @size pixels centered on @pixel, shifted to fit an image of @width by @height
+ */
+pub fn probe_region(pixel: [u32; 2], width: u32, height: u32, size: u32) -> [u32; 4] {
+    let fit = |p: u32, extent: u32| {
+        let size = size.min(extent);
+        (p.saturating_sub(size / 2)).min(extent - size)
+    };
+    [fit(pixel[0], width), fit(pixel[1], height), size.min(width), size.min(height)]
+}
+
+/**
+ *  Shows the values of the hovered pixel in every image, with a magnifier around it.
+ */
+pub struct Inspector {
+    pub open: bool,
+    /// Hovered position on the window in physical pixels, which map 1:1 to image pixels
+    hover: Option<[f32; 2]>,
+    /// Pixels drawn per image pixel in the magnifier
+    zoom: f32,
+    /// Image shown in the magnifier
+    image: usize,
+    probe: Option<Probe>,
+    pending: bool,
+    sender: Sender<Probe>,
+    receiver: Receiver<Probe>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Inspector {
+        let (sender, receiver) = channel();
+        Self {
+            open: false,
+            hover: None,
+            zoom: 12.0,
+            image: 0,
+            probe: None,
+            pending: false,
+            sender,
+            receiver,
+        }
+    }
+
+    /*
+     * Pixel to probe in images of @width by @height, at most one probe is read back at a time
+     */
+    pub fn request(&mut self, width: u32, height: u32) -> Option<[u32; 2]> {
+        if !self.open || self.pending {
+            return None;
+        }
+        let [x, y] = self.hover?;
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            return None;
+        }
+        self.pending = true;
+        Some([x as u32, y as u32])
+    }
+
+    /*
+     * Channel to send the requested probe over once it's read back
+     */
+    pub fn sender(&self) -> Sender<Probe> {
+        self.sender.clone()
+    }
+
+    pub fn show(&mut self, context: &Context) {
+        if let Some(probe) = self.receiver.try_iter().last() {
+            self.probe = Some(probe);
+            self.pending = false;
+        }
+        if !self.open {
+            return;
+        }
+
+        // Only probe the output, not the GUI on top of it
+        self.hover = match context.pointer_hover_pos() {
+            Some(pos) if !context.is_pointer_over_area() => Some([pos.x * context.pixels_per_point(), pos.y * context.pixels_per_point()]),
+            _ => self.hover,
+        };
+
+        let mut open = self.open;
+        egui::Window::new("Pixel inspector")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(context, |ui| {
+                let Some(probe) = &self.probe else {
+                    ui.label("Hover over the output to inspect its pixels");
+                    return;
+                };
+
+                ui.label(format!("Pixel ({}, {})", probe.pixel[0], probe.pixel[1]));
+                Self::values(ui, probe, &mut self.image);
+                ui.separator();
+                ui.add(egui::Slider::new(&mut self.zoom, 4.0..=32.0).text("zoom"));
+                self.image = self.image.min(probe.images.len().saturating_sub(1));
+                self.zoom = Self::magnifier(ui, probe, self.image, self.zoom);
+            });
+        self.open = open;
+    }
+
+    /*
+     * Table with the values of the probed pixel in every image, clicking an image shows it in the magnifier
+     */
+    fn values(ui: &mut Ui, probe: &Probe, selected: &mut usize) {
+        Grid::new("pixel_values").striped(true).num_columns(2).show(ui, |ui| {
+            for (i, image) in probe.images.iter().enumerate() {
+                ui.selectable_value(selected, i, &image.name);
+                match probe.value(i, probe.pixel) {
+                    Some([r, g, b, a]) => ui.monospace(format!("{:.4} {:.4} {:.4} {:.4}", r, g, b, a)),
+                    None => ui.label("-"),
+                };
+                ui.end_row();
+            }
+        });
+    }
+
+    /*
+     * Draw the probed region of @image enlarged by @zoom, scrolling over it changes the zoom
+     */
+    fn magnifier(ui: &mut Ui, probe: &Probe, image: usize, zoom: f32) -> f32 {
+        let [x, y, width, height] = probe.region;
+        let (response, painter) = ui.allocate_painter(vec2(width as f32 * zoom, height as f32 * zoom), Sense::hover());
+        let origin = response.rect.min;
+        for py in 0..height {
+            for px in 0..width {
+                let Some([r, g, b, _]) = probe.value(image, [x + px, y + py]) else {
+                    continue;
+                };
+                // Images hold linear color, shown sRGB encoded like the output
+                let rect = Rect::from_min_size(origin + vec2(px as f32 * zoom, py as f32 * zoom), vec2(zoom, zoom));
+                painter.rect_filled(rect, 0.0, Color32::from(Rgba::from_rgb(r, g, b)));
+            }
+        }
+
+        let center = pos2((probe.pixel[0] - x) as f32 * zoom, (probe.pixel[1] - y) as f32 * zoom);
+        painter.rect_stroke(Rect::from_min_size(origin + center.to_vec2(), vec2(zoom, zoom)), 0.0, Stroke::new(2.0, Color32::WHITE));
+
+        if response.hovered() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            return (zoom + scroll * 0.05).clamp(4.0, 32.0);
+        }
+        zoom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * Probe of one image whose pixels hold their x and y in the red and green channel
+     */
+    fn probe(region: [u32; 4]) -> Probe {
+        let [x, y, width, height] = region;
+        let data = (y..y + height)
+            .flat_map(|py| (x..x + width).flat_map(move |px| [px as f32, py as f32, 0.0, 1.0]))
+            .collect();
+        Probe {
+            pixel: [x, y],
+            region,
+            images: Vec::from([ProbeImage { name: "image".to_string(), data }]),
+        }
+    }
+
+    #[test]
+    fn region_is_centered_on_the_pixel() {
+        assert_eq!(probe_region([50, 60], 100, 100, 15), [43, 53, 15, 15]);
+    }
+
+    #[test]
+    fn region_is_clamped_at_edges() {
+        assert_eq!(probe_region([2, 50], 100, 100, 15), [0, 43, 15, 15]);
+        assert_eq!(probe_region([50, 98], 100, 100, 15), [43, 85, 15, 15]);
+    }
+
+    #[test]
+    fn region_is_clamped_at_corners() {
+        assert_eq!(probe_region([0, 0], 100, 80, 15), [0, 0, 15, 15]);
+        assert_eq!(probe_region([99, 79], 100, 80, 15), [85, 65, 15, 15]);
+        // Pixels past the image keep the region inside it
+        assert_eq!(probe_region([500, 500], 100, 80, 15), [85, 65, 15, 15]);
+    }
+
+    #[test]
+    fn region_larger_than_the_image_covers_it() {
+        assert_eq!(probe_region([3, 1], 10, 4, 15), [0, 0, 10, 4]);
+    }
+
+    #[test]
+    fn values_map_pixels_into_the_region() {
+        let probe = probe([43, 53, 15, 15]);
+        assert_eq!(probe.value(0, [43, 53]), Some([43.0, 53.0, 0.0, 1.0]));
+        assert_eq!(probe.value(0, [50, 60]), Some([50.0, 60.0, 0.0, 1.0]));
+        assert_eq!(probe.value(0, [57, 67]), Some([57.0, 67.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn values_outside_the_region_are_none() {
+        let probe = probe([43, 53, 15, 15]);
+        assert_eq!(probe.value(0, [42, 60]), None);
+        assert_eq!(probe.value(0, [50, 52]), None);
+        assert_eq!(probe.value(0, [58, 60]), None);
+        assert_eq!(probe.value(0, [50, 68]), None);
+    }
+
+    #[test]
+    fn values_of_missing_images_are_none() {
+        let mut probe = probe([0, 0, 4, 4]);
+        assert_eq!(probe.value(1, [1, 1]), None);
+        // Short data, e.g. from a failed readback
+        probe.images[0].data.truncate(8);
+        assert_eq!(probe.value(0, [1, 0]), Some([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(probe.value(0, [2, 0]), None);
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt;
+use std::fs;
+use crate::Error;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    Smoothstep,
+    /// Cubic bezier easing through (0, 0), (x1, y1), (x2, y2) and (1, 1), like CSS' cubic-bezier
+    Bezier(f32, f32, f32, f32),
+}
+
+impl Interpolation {
+    /*
+     * Map the progress @t between two keyframes, in [0, 1], to an interpolation factor
+     */
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Interpolation::Bezier(x1, y1, x2, y2) => {
+                // Find the curve parameter for which x equals t, x is monotonic for x1, x2 in [0, 1]
+                let (mut low, mut high) = (0.0f32, 1.0f32);
+                for _ in 0..32 {
+                    let mid = (low + high) * 0.5;
+                    if cubic_bezier(x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0), mid) < t {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                cubic_bezier(y1, y2, (low + high) * 0.5)
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Step => "step",
+            Interpolation::Linear => "linear",
+            Interpolation::Smoothstep => "smoothstep",
+            Interpolation::Bezier(..) => "bezier",
+        }
+    }
+}
+
+/*
+ * One dimensional cubic bezier with end points 0 and 1
+ */
+fn cubic_bezier(p1: f32, p2: f32, t: f32) -> f32 {
+    let s = 1.0 - t;
+    3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t
+}
+
+/**
+ *  A value at a point in time, @interpolation describes the curve towards the next keyframe.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+    pub interpolation: Interpolation,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, value: f32, interpolation: Interpolation) -> Keyframe {
+        Self {
+            time,
+            value,
+            interpolation,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct Track {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(name: &str) -> Track {
+        Self {
+            name: name.to_string(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    /*
+     * Add a keyframe at @time, keeping the keyframes ordered by time
+     */
+    pub fn keyframe(mut self, time: f32, value: f32, interpolation: Interpolation) -> Self {
+        self.insert(Keyframe::new(time, value, interpolation));
+        self
+    }
+
+    /*
+     * Value of the track at @time, the first and last keyframes are held outside of the animated range
+     */
+    pub fn evaluate(&self, time: f32) -> f32 {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        match (next.checked_sub(1).map(|i| &self.keyframes[i]), self.keyframes.get(next)) {
+            (None, None) => 0.0,
+            (None, Some(k)) | (Some(k), None) => k.value,
+            (Some(a), Some(b)) => {
+                let t = (time - a.time) / (b.time - a.time);
+                let f = a.interpolation.apply(t);
+                a.value + (b.value - a.value) * f
+            }
+        }
+    }
+
+    /*
+     * Add a keyframe, replacing the one at the same time if there is one
+     */
+    pub fn insert(&mut self, keyframe: Keyframe) -> usize {
+        match self.keyframes.iter().position(|k| k.time == keyframe.time) {
+            Some(i) => {
+                self.keyframes[i] = keyframe;
+                i
+            },
+            None => {
+                let i = self.keyframes.partition_point(|k| k.time < keyframe.time);
+                self.keyframes.insert(i, keyframe);
+                i
+            }
+        }
+    }
+
+    /*
+     * Restore the time ordering after keyframes were moved, returns the new index of @index
+     */
+    pub fn sort(&mut self, index: usize) -> usize {
+        let moved = self.keyframes[index];
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.keyframes.iter().position(|k| *k == moved).unwrap_or(index)
+    }
+}
+
+/**
+ *  Keyframe tracks of named parameters. Evaluation is a pure function of time, so exports are deterministic.
+ *
+ *  The text format has a `track <name>` line per track followed by one keyframe per line:
+ *  `<time> <value> <step|linear|smoothstep|bezier x1 y1 x2 y2>`. Lines starting with `#` are ignored.
+ */
+#[derive(Clone, Default, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct Animation {
+    pub tracks: Vec<Track>,
+}
+
+impl Animation {
+    pub fn track(mut self, track: Track) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    pub fn load(path: &str) -> Result<Animation, Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        Ok(fs::write(path, self.to_string())?)
+    }
+
+    pub fn parse(source: &str) -> Result<Animation, Error> {
+        let mut animation = Animation::default();
+        for (number, line) in source.lines().enumerate() {
+            let err = |message: &str| Error::Config(format!("Animation line {}: {}", number + 1, message));
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            let number = |i: usize| -> Result<f32, Error> {
+                words.get(i).ok_or(err("missing value"))?.parse::<f32>().map_err(|_| err("invalid number"))
+            };
+
+            match words.first() {
+                None => continue,
+                Some(w) if w.starts_with('#') => continue,
+                Some(&"track") => {
+                    let name = line.trim().trim_start_matches("track").trim();
+                    if name.is_empty() {
+                        return Err(err("missing track name"));
+                    }
+                    animation.tracks.push(Track::new(name));
+                },
+                Some(_) => {
+                    let interpolation = match words.get(2) {
+                        None | Some(&"linear") => Interpolation::Linear,
+                        Some(&"step") => Interpolation::Step,
+                        Some(&"smoothstep") => Interpolation::Smoothstep,
+                        Some(&"bezier") => Interpolation::Bezier(number(3)?, number(4)?, number(5)?, number(6)?),
+                        Some(other) => return Err(err(&format!("unknown interpolation \"{}\"", other))),
+                    };
+                    let keyframe = Keyframe {
+                        time: number(0)?,
+                        value: number(1)?,
+                        interpolation,
+                    };
+                    animation.tracks.last_mut().ok_or(err("keyframe before the first track"))?.insert(keyframe);
+                }
+            }
+        }
+
+        Ok(animation)
+    }
+
+    /*
+     * Values of all tracks at @time, in track order
+     */
+    pub fn evaluate(&self, time: f32) -> Vec<f32> {
+        self.tracks.iter().map(|t| t.evaluate(time)).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.name == name)
+    }
+
+    /*
+     * Shader macros mapping each track name to its index in the parameter buffer, e.g. PARAM_BRIGHTNESS
+     */
+    pub fn macros(&self) -> Vec<(String, String)> {
+        let mut macros = self.tracks.iter().enumerate()
+            .map(|(i, t)| (format!("PARAM_{}", macro_name(&t.name)), i.to_string()))
+            .collect::<Vec<(String, String)>>();
+        macros.push(("NUM_PARAMS".to_string(), self.tracks.len().to_string()));
+        macros
+    }
+}
+
+/*
+ * Uppercase @name and replace everything that can't be part of a GLSL identifier
+ */
+pub fn macro_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+impl fmt::Display for Animation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for track in &self.tracks {
+            writeln!(f, "track {}", track.name)?;
+            for k in &track.keyframes {
+                match k.interpolation {
+                    Interpolation::Bezier(x1, y1, x2, y2) => writeln!(f, "{} {} bezier {} {} {} {}", k.time, k.value, x1, y1, x2, y2)?,
+                    i => writeln!(f, "{} {} {}", k.time, k.value, i.name())?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::clock::Clock;
+
+    fn keyframe(time: f32, value: f32, interpolation: Interpolation) -> Keyframe {
+        Keyframe { time, value, interpolation }
+    }
+
+    fn track(interpolation: Interpolation) -> Track {
+        Track {
+            name: "value".to_string(),
+            keyframes: Vec::from([ keyframe(1.0, 2.0, interpolation), keyframe(3.0, 6.0, Interpolation::Linear) ]),
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn step_holds_until_the_next_keyframe() {
+        let track = track(Interpolation::Step);
+        assert_eq!(track.evaluate(1.0), 2.0);
+        assert_eq!(track.evaluate(2.999), 2.0);
+        assert_eq!(track.evaluate(3.0), 6.0);
+    }
+
+    #[test]
+    fn linear_interpolates_proportionally() {
+        let track = track(Interpolation::Linear);
+        assert!(close(track.evaluate(1.5), 3.0));
+        assert!(close(track.evaluate(2.0), 4.0));
+        assert!(close(track.evaluate(2.5), 5.0));
+    }
+
+    #[test]
+    fn smoothstep_eases_in_and_out() {
+        let track = track(Interpolation::Smoothstep);
+        assert!(close(track.evaluate(2.0), 4.0));
+        assert!(close(track.evaluate(1.5), 2.0 + 4.0 * 0.15625));
+        assert!(close(track.evaluate(2.5), 2.0 + 4.0 * 0.84375));
+    }
+
+    #[test]
+    fn bezier_matches_its_control_points() {
+        // Control points on the diagonal give a linear curve
+        assert!(close(Interpolation::Bezier(0.25, 0.25, 0.75, 0.75).apply(0.3), 0.3));
+        // CSS ease-in starts slow and ends at 1
+        let ease_in = Interpolation::Bezier(0.42, 0.0, 1.0, 1.0);
+        assert!(ease_in.apply(0.5) < 0.5);
+        assert!(close(ease_in.apply(0.0), 0.0));
+        assert!(close(ease_in.apply(1.0), 1.0));
+        let track = track(ease_in);
+        assert!(close(track.evaluate(2.0), 2.0 + 4.0 * ease_in.apply(0.5)));
+    }
+
+    #[test]
+    fn apply_clamps_the_progress() {
+        for interpolation in [Interpolation::Linear, Interpolation::Smoothstep, Interpolation::Bezier(0.1, 0.7, 0.2, 1.0)] {
+            assert!(close(interpolation.apply(-1.0), 0.0));
+            assert!(close(interpolation.apply(2.0), 1.0));
+        }
+        assert_eq!(Interpolation::Step.apply(2.0), 0.0);
+    }
+
+    #[test]
+    fn first_and_last_keyframes_are_held() {
+        let track = track(Interpolation::Linear);
+        assert_eq!(track.evaluate(-5.0), 2.0);
+        assert_eq!(track.evaluate(0.999), 2.0);
+        assert_eq!(track.evaluate(3.0), 6.0);
+        assert_eq!(track.evaluate(100.0), 6.0);
+        assert_eq!(Track::new("empty").evaluate(1.0), 0.0);
+    }
+
+    #[test]
+    fn evaluation_follows_the_looping_clock() {
+        let animation = Animation { tracks: Vec::from([ track(Interpolation::Linear) ]) };
+        let mut clock = Clock::new();
+        clock.set_loop_region(Some((1.0, 3.0)));
+        clock.seek(1.5);
+        clock.advance(2.0);
+        assert!(close(clock.time(), 1.5));
+        assert_eq!(animation.evaluate(clock.time()), animation.evaluate(1.5));
+        clock.advance(-1.0);
+        assert!(close(clock.time(), 2.5));
+        assert!(close(animation.evaluate(clock.time())[0], 5.0));
+    }
+
+    #[test]
+    fn evaluation_is_pure() {
+        let animation = Animation { tracks: Vec::from([ track(Interpolation::Smoothstep), track(Interpolation::Step) ]) };
+        let values = animation.evaluate(2.25);
+        animation.evaluate(0.0);
+        animation.evaluate(10.0);
+        assert_eq!(animation.evaluate(2.25), values);
+    }
+
+    #[test]
+    fn parse_display_round_trip() {
+        let source = "# comment\ntrack brightness\n0 0 step\n1 0.5 smoothstep\n2 1 bezier 0.42 0 0.58 1\n3 0.25\n\ntrack hue shift\n0.5 -1 linear\n";
+        let animation = Animation::parse(source).unwrap();
+        assert_eq!(animation.tracks.len(), 2);
+        assert_eq!(animation.tracks[1].name, "hue shift");
+        assert_eq!(animation.tracks[0].keyframes[0].interpolation, Interpolation::Step);
+        assert_eq!(animation.tracks[0].keyframes[2].interpolation, Interpolation::Bezier(0.42, 0.0, 0.58, 1.0));
+        assert_eq!(animation.tracks[0].keyframes[3].interpolation, Interpolation::Linear);
+
+        let written = animation.to_string();
+        let reparsed = Animation::parse(&written).unwrap();
+        assert_eq!(reparsed, animation);
+        assert_eq!(reparsed.to_string(), written);
+    }
+
+    #[test]
+    fn parse_sorts_keyframes() {
+        let animation = Animation::parse("track a\n2 1\n0 0\n1 5\n").unwrap();
+        let times = animation.tracks[0].keyframes.iter().map(|k| k.time).collect::<Vec<f32>>();
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn parse_reports_errors() {
+        assert!(Animation::parse("0 1\n").is_err());
+        assert!(Animation::parse("track\n").is_err());
+        assert!(Animation::parse("track a\n0 x\n").is_err());
+        assert!(Animation::parse("track a\n0 1 cubic\n").is_err());
+        assert!(Animation::parse("track a\n0 1 bezier 0 0 1\n").is_err());
+    }
+}
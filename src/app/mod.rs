@@ -1,9 +1,27 @@
+#[allow(clippy::module_inception)]
 pub mod app;
 pub mod draw_orch;
 pub mod cpal_wrapper;
 pub mod audio_orch;
-mod png;
+pub mod clock;
+pub mod effects;
+pub mod hook;
+pub mod image;
+pub mod input;
+pub mod keyframes;
+pub mod pipeline;
+pub mod readback;
+pub mod reflect;
+mod curve_editor;
+mod export;
+mod inspector;
+mod profiler;
+mod reconfigure;
+mod shader_editor;
+mod shader_errors;
+mod visualize;
+mod wav;
 
 pub use self::draw_orch::DrawOrchestrator;
 pub use self::app::App;
-pub use self::cpal_wrapper::StreamFactory;
\ No newline at end of file
+pub use self::cpal_wrapper::{output_device_names, StreamFactory};
\ No newline at end of file
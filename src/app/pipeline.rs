@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+use ash::vk;
+use cen::vulkan::{Device, GpuHandle, Pipeline};
+use log::{trace, warn};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{DebounceEventResult, Debouncer};
+use crate::app::effects;
+use crate::Error;
+
+/**
+ *  Value of a specialization constant, bools are passed as VkBool32.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SpecValue {
+    Bool(bool),
+    Int(i32),
+    UInt(u32),
+    Float(f32),
+}
+
+impl SpecValue {
+    pub fn bytes(&self) -> [u8; 4] {
+        match *self {
+            SpecValue::Bool(v) => (v as u32).to_ne_bytes(),
+            SpecValue::Int(v) => v.to_ne_bytes(),
+            SpecValue::UInt(v) => v.to_ne_bytes(),
+            SpecValue::Float(v) => v.to_ne_bytes(),
+        }
+    }
+}
+
+/**
+ *  A shader constant declared as `layout( constant_id = <id> ) const ...`. Changing its value only rebuilds the
+ *  pipeline, the shader isn't compiled again.
+ */
+#[derive(Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct SpecConstant {
+    pub id: u32,
+    /// Label in the GUI
+    pub name: String,
+    pub value: SpecValue,
+}
+
+impl SpecConstant {
+    pub fn new(id: u32, name: &str, value: SpecValue) -> SpecConstant {
+        Self {
+            id,
+            name: name.to_string(),
+            value,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ShaderLanguage {
+    Glsl,
+    Hlsl,
+    Wgsl,
+    /// Precompiled SPIR-V, macros don't apply
+    SpirV,
+}
+
+impl ShaderLanguage {
+    /*
+     * Language of the shader at @path, by its extension
+     */
+    pub fn from_path(path: &Path) -> Result<ShaderLanguage, Error> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("comp" | "glsl") => Ok(ShaderLanguage::Glsl),
+            Some("hlsl") => Ok(ShaderLanguage::Hlsl),
+            Some("wgsl") => Ok(ShaderLanguage::Wgsl),
+            Some("spv") => Ok(ShaderLanguage::SpirV),
+            _ => Err(Error::Shader(format!("Unknown shader type of {}, expected .comp, .glsl, .hlsl, .wgsl or .spv", path.display()))),
+        }
+    }
+}
+
+/*
+ * Compile the compute shader at @path to SPIR-V with @macros defined, the language is chosen by the extension
+ */
+pub fn compile_shader(path: &Path, macros: &HashMap<String, String>) -> Result<Vec<u32>, Error> {
+    let language = ShaderLanguage::from_path(path)?;
+    let code = match language {
+        ShaderLanguage::SpirV => {
+            let bytes = fs::read(path)
+                .map_err(|e| Error::Shader(format!("Failed to read {}: {}", path.display(), e)))?;
+            ash::util::read_spv(&mut Cursor::new(bytes))
+                .map_err(|e| Error::Shader(format!("Invalid SPIR-V in {}: {}", path.display(), e)))?
+        },
+        ShaderLanguage::Wgsl => compile_wgsl(path, &read_source(path)?, macros)?,
+        ShaderLanguage::Glsl | ShaderLanguage::Hlsl => compile_shaderc(path, &read_source(path)?, language, macros)?,
+    };
+
+    trace!("Compiled shader: {}", path.display());
+    Ok(code)
+}
+
+/*
+ * Source of the shader at @path, built-in shaders are looked up instead of read
+ */
+pub fn read_source(path: &Path) -> Result<String, Error> {
+    if let Some(source) = path.to_str().and_then(effects::builtin_source) {
+        return Ok(source.to_string());
+    }
+    fs::read_to_string(path).map_err(|e| Error::Shader(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+fn compile_shaderc(path: &Path, source: &str, language: ShaderLanguage, macros: &HashMap<String, String>) -> Result<Vec<u32>, Error> {
+    let compiler = shaderc::Compiler::new()
+        .ok_or(Error::Shader("Failed to create the shader compiler".to_string()))?;
+    let mut options = shaderc::CompileOptions::new()
+        .ok_or(Error::Shader("Failed to create the shader compile options".to_string()))?;
+    if language == ShaderLanguage::Hlsl {
+        options.set_source_language(shaderc::SourceLanguage::HLSL);
+    }
+    options.add_macro_definition("EP", Some("main"));
+    for (k, v) in macros {
+        options.add_macro_definition(k, Some(v));
+    }
+
+    let binary = compiler.compile_into_spirv(
+        source,
+        shaderc::ShaderKind::Compute,
+        &path.to_string_lossy(),
+        "main",
+        Some(&options)
+    ).map_err(|e| Error::Shader(e.to_string()))?;
+
+    Ok(binary.as_binary().to_vec())
+}
+
+/// Macros of kiyo that only GLSL and HLSL shaders get, WGSL shaders spell out the texel format instead
+const WGSL_SKIPPED_MACROS: [&str; 1] = ["IMAGE_FORMAT"];
+
+/*
+ * Compile WGSL @source, read from @path, to SPIR-V. WGSL has no preprocessor, numeric and boolean macros are declared
+ * as constants instead. They're appended, declaration order doesn't matter in WGSL and the line numbers in errors stay
+ * correct. Other macros can't be expressed and are an error.
+ */
+pub fn compile_wgsl(path: &Path, source: &str, macros: &HashMap<String, String>) -> Result<Vec<u32>, Error> {
+    let macros = macros.iter()
+        .filter(|(k, _)| !WGSL_SKIPPED_MACROS.contains(&k.as_str()))
+        .collect::<Vec<(&String, &String)>>();
+    let expressible = |v: &str| v.parse::<i64>().is_ok() || v.parse::<f64>().is_ok() || v == "true" || v == "false";
+    let mut unsupported = macros.iter()
+        .filter(|(_, v)| !expressible(v))
+        .map(|(k, v)| format!("{} = \"{}\"", k, v))
+        .collect::<Vec<String>>();
+    if !unsupported.is_empty() {
+        unsupported.sort();
+        return Err(Error::Shader(format!(
+            "{}: WGSL shaders only get numeric and boolean defines, these can't be WGSL constants: {}",
+            path.display(), unsupported.join(", ")
+        )));
+    }
+
+    let mut constants = macros.iter()
+        .map(|(k, v)| format!("const {} = {};\n", k, v))
+        .collect::<Vec<String>>();
+    constants.sort();
+    let source = format!("{}\n{}", source, constants.concat());
+
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|e| Error::Shader(e.emit_to_string_with_path(&source, path)))?;
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|e| Error::Shader(e.emit_to_string_with_path(&source, &path.to_string_lossy())))?;
+
+    let options = naga::back::spv::Options {
+        lang_version: (1, 3),
+        ..Default::default()
+    };
+    let pipeline_options = naga::back::spv::PipelineOptions {
+        shader_stage: naga::ShaderStage::Compute,
+        entry_point: "main".to_string(),
+    };
+    naga::back::spv::write_vec(&module, &info, &options, Some(&pipeline_options))
+        .map_err(|e| Error::Shader(format!("Failed to translate {} to SPIR-V: {}", path.display(), e)))
+}
+
+/**
+ *  Push descriptor set layout owned by kiyo, cen doesn't expose the handle of its layouts.
+ */
+pub struct PushDescriptorLayout {
+    device: Device,
+    layout: vk::DescriptorSetLayout,
+}
+
+impl PushDescriptorLayout {
+    pub fn new(device: &Device, bindings: &[vk::DescriptorSetLayoutBinding]) -> Result<PushDescriptorLayout, Error> {
+        let create_info = vk::DescriptorSetLayoutCreateInfo::default()
+            .flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
+            .bindings(bindings);
+        let layout = unsafe { device.handle().create_descriptor_set_layout(&create_info, None) }
+            .map_err(|e| Error::Gpu(format!("Failed to create descriptor set layout: {}", e)))?;
+
+        Ok(Self {
+            device: device.clone(),
+            layout,
+        })
+    }
+
+    pub fn handle(&self) -> vk::DescriptorSetLayout {
+        self.layout
+    }
+}
+
+impl Drop for PushDescriptorLayout {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle().destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
+
+struct ComputePipelineInner {
+    device: Device,
+    _descriptor_layout: Arc<PushDescriptorLayout>,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl Drop for ComputePipelineInner {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle().destroy_pipeline(self.pipeline, None);
+            self.device.handle().destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+impl GpuHandle for ComputePipelineInner {}
+
+/**
+ *  Compute pipeline created from SPIR-V, unlike cen's pipelines it supports specialization constants.
+ *  Command buffers keep it alive while they use it, so it can be replaced at any time.
+ */
+#[derive(Clone)]
+pub struct ComputePipeline {
+    inner: Arc<ComputePipelineInner>,
+}
+
+impl Pipeline for ComputePipeline {
+    fn handle(&self) -> vk::Pipeline {
+        self.inner.pipeline
+    }
+
+    fn bind_point(&self) -> vk::PipelineBindPoint {
+        vk::PipelineBindPoint::COMPUTE
+    }
+
+    fn layout(&self) -> vk::PipelineLayout {
+        self.inner.layout
+    }
+
+    fn reference(&self) -> Arc<dyn GpuHandle> {
+        self.inner.clone()
+    }
+}
+
+impl ComputePipeline {
+    pub fn new(
+        device: &Device,
+        code: &[u32],
+        descriptor_layout: &Arc<PushDescriptorLayout>,
+        push_constant_ranges: &[vk::PushConstantRange],
+        constants: &[SpecConstant]
+    ) -> Result<ComputePipeline, Error> {
+        let handle = device.handle();
+
+        let shader_module = unsafe {
+            handle.create_shader_module(&vk::ShaderModuleCreateInfo::default().code(code), None)
+        }.map_err(|e| Error::Shader(format!("Failed to create shader module: {}", e)))?;
+
+        let data = constants.iter().flat_map(|c| c.value.bytes()).collect::<Vec<u8>>();
+        let entries = constants.iter().enumerate()
+            .map(|(i, c)| vk::SpecializationMapEntry::default()
+                .constant_id(c.id)
+                .offset(i as u32 * 4)
+                .size(4))
+            .collect::<Vec<vk::SpecializationMapEntry>>();
+        let specialization = vk::SpecializationInfo::default()
+            .map_entries(&entries)
+            .data(&data);
+
+        let entry_point = CString::new("main").unwrap();
+        let stage = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader_module)
+            .name(&entry_point)
+            .specialization_info(&specialization);
+
+        let set_layouts = [descriptor_layout.handle()];
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(push_constant_ranges);
+
+        let result = unsafe {
+            handle.create_pipeline_layout(&layout_info, None)
+                .map_err(|e| Error::Shader(format!("Failed to create pipeline layout: {}", e)))
+                .and_then(|layout| {
+                    let create_info = vk::ComputePipelineCreateInfo::default()
+                        .stage(stage)
+                        .layout(layout);
+                    match handle.create_compute_pipelines(vk::PipelineCache::null(), &[create_info], None) {
+                        Ok(pipelines) => Ok((layout, pipelines[0])),
+                        Err((_, e)) => {
+                            handle.destroy_pipeline_layout(layout, None);
+                            Err(Error::Shader(format!("Failed to create compute pipeline: {}", e)))
+                        }
+                    }
+                })
+        };
+        unsafe { handle.destroy_shader_module(shader_module, None); }
+
+        let (layout, pipeline) = result?;
+        Ok(Self {
+            inner: Arc::new(ComputePipelineInner {
+                device: device.clone(),
+                _descriptor_layout: descriptor_layout.clone(),
+                layout,
+                pipeline,
+            })
+        })
+    }
+}
+
+/**
+ *  Watches shader files and reports the ones that changed since the last poll.
+ */
+pub struct ShaderWatcher {
+    debouncer: Option<Debouncer<RecommendedWatcher>>,
+    receiver: Receiver<DebounceEventResult>,
+    directories: Vec<PathBuf>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> ShaderWatcher {
+        let (sender, receiver) = channel();
+        let debouncer = notify_debouncer_mini::new_debouncer(Duration::from_millis(250), sender)
+            .inspect_err(|e| warn!("Failed to create shader watcher, hot-reloading is disabled: {}", e))
+            .ok();
+
+        Self {
+            debouncer,
+            receiver,
+            directories: Vec::new(),
+        }
+    }
+
+    /*
+     * Watch the directory of @path, editors often replace files instead of writing to them
+     */
+    pub fn watch(&mut self, path: &Path) {
+        let Some(directory) = canonical(path).parent().map(Path::to_path_buf) else {
+            return;
+        };
+        if self.directories.contains(&directory) {
+            return;
+        }
+        if let Some(debouncer) = &mut self.debouncer {
+            match debouncer.watcher().watch(&directory, RecursiveMode::NonRecursive) {
+                Ok(()) => self.directories.push(directory),
+                Err(e) => warn!("Failed to watch {}: {}", directory.display(), e),
+            }
+        }
+    }
+
+    /*
+     * Canonical paths of the files changed in the watched directories
+     */
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for result in self.receiver.try_iter() {
+            match result {
+                Ok(events) => paths.extend(events.into_iter().map(|e| canonical(&e.path))),
+                Err(e) => warn!("Shader watcher error: {}", e),
+            }
+        }
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/*
+ * Absolute @path with symlinks resolved, or @path itself when it doesn't exist
+ */
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "@compute @workgroup_size(1)\nfn main() {\n    let x = SIZE * 2;\n}\n";
+
+    fn macros(defines: &[(&str, &str)]) -> HashMap<String, String> {
+        defines.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn numeric_and_boolean_defines_are_constants() {
+        let defines = macros(&[("SIZE", "4"), ("SCALE", "0.5"), ("OFFSET", "-3"), ("ENABLED", "true")]);
+        assert!(compile_wgsl(Path::new("a.wgsl"), SHADER, &defines).is_ok());
+    }
+
+    #[test]
+    fn image_format_is_skipped() {
+        let defines = macros(&[("SIZE", "4"), ("IMAGE_FORMAT", "rgba16f")]);
+        assert!(compile_wgsl(Path::new("a.wgsl"), SHADER, &defines).is_ok());
+    }
+
+    #[test]
+    fn other_defines_are_an_error() {
+        let defines = macros(&[("SIZE", "4"), ("MODE", "blur"), ("KERNEL", "vec3(1.0)")]);
+        let Err(Error::Shader(message)) = compile_wgsl(Path::new("a.wgsl"), SHADER, &defines) else {
+            panic!("Non-numeric defines were accepted");
+        };
+        assert!(message.contains("KERNEL = \"vec3(1.0)\", MODE = \"blur\""), "{}", message);
+    }
+}
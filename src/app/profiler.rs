@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+use std::fs;
+use ash::vk;
+use cen::vulkan::{CommandBuffer, Device};
+use egui::{Context, Grid, Ui};
+use log::{error, info};
+
+/// Frames the averages in the profiler are taken over
+const AVERAGE_FRAMES: usize = 60;
+/// Passes recorded for a trace at most, so a forgotten recording doesn't eat all memory
+const MAX_TRACE_EVENTS: usize = 1_000_000;
+
+/**
+ *  A pass dispatched in a profiled frame.
+ */
+#[derive(Clone, Debug)]
+pub struct PassSample {
+    pub name: String,
+    pub dispatch: [u32; 3],
+    /// Bytes of the images the pass reads and writes
+    pub memory: u64,
+}
+
+/**
+ *  GPU time a pass took.
+ */
+#[derive(Clone, Debug)]
+pub struct PassTiming {
+    pub sample: PassSample,
+    /// GPU timestamp of the start of the pass in microseconds
+    pub start: f64,
+    /// Duration of the pass in microseconds
+    pub duration: f64,
+}
+
+/**
+ *  Timestamp queries around the passes of one frame in flight.
+ */
+pub struct TimestampPool {
+    device: Device,
+    pool: vk::QueryPool,
+    capacity: u32,
+    /// Nanoseconds per timestamp tick
+    period: f64,
+    /// Amount of meaningful low bits in a timestamp of the queue
+    valid_bits: u32,
+    /// Passes recorded since the last reset, two timestamps each
+    passes: Vec<PassSample>,
+}
+
+impl TimestampPool {
+    /*
+     * Pool for @passes passes, @period is the timestamp period of the device and @valid_bits the timestamp valid bits
+     * of the queue family
+     */
+    pub fn new(device: &Device, passes: u32, period: f32, valid_bits: u32) -> Result<TimestampPool, vk::Result> {
+        let create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(passes * 2);
+        let pool = unsafe { device.handle().create_query_pool(&create_info, None) }?;
+
+        Ok(Self {
+            device: device.clone(),
+            pool,
+            capacity: passes,
+            period: period as f64,
+            valid_bits,
+            passes: Vec::new(),
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /*
+     * Forget the previous frame's passes, must be recorded before any pass
+     */
+    pub fn reset(&mut self, command_buffer: &CommandBuffer) {
+        self.passes.clear();
+        unsafe { self.device.handle().cmd_reset_query_pool(command_buffer.handle(), self.pool, 0, self.capacity * 2) };
+    }
+
+    /*
+     * Write the start timestamp of @sample once the compute work recorded before it is done, passes are separated by
+     * barriers so that's the previous pass. Returns false when the pool is full
+     */
+    pub fn begin(&mut self, command_buffer: &CommandBuffer, sample: PassSample) -> bool {
+        let index = self.passes.len() as u32;
+        if index >= self.capacity {
+            return false;
+        }
+        self.passes.push(sample);
+        unsafe { self.device.handle().cmd_write_timestamp(command_buffer.handle(), vk::PipelineStageFlags::COMPUTE_SHADER, self.pool, index * 2) };
+        true
+    }
+
+    /*
+     * Write the end timestamp of the last pass once its dispatch is done
+     */
+    pub fn end(&self, command_buffer: &CommandBuffer) {
+        let index = self.passes.len() as u32 - 1;
+        unsafe { self.device.handle().cmd_write_timestamp(command_buffer.handle(), vk::PipelineStageFlags::COMPUTE_SHADER, self.pool, index * 2 + 1) };
+    }
+
+    /*
+     * Timings of the recorded passes, None while the frame hasn't finished rendering
+     */
+    pub fn read(&self) -> Option<Vec<PassTiming>> {
+        if self.passes.is_empty() {
+            return None;
+        }
+
+        let mut ticks = vec![0u64; self.passes.len() * 2];
+        unsafe { self.device.handle().get_query_pool_results(self.pool, 0, &mut ticks, vk::QueryResultFlags::TYPE_64) }.ok()?;
+        Some(timings(&self.passes, &ticks, self.period, self.valid_bits))
+    }
+}
+
+impl Drop for TimestampPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle().destroy_query_pool(self.pool, None);
+        }
+    }
+}
+
+/*
+ * Timings of @passes from their start and end @ticks, with @period nanoseconds per tick. Only the low @valid_bits of a
+ * tick are defined, the counter wraps around at that width.
+ */
+pub fn timings(passes: &[PassSample], ticks: &[u64], period: f64, valid_bits: u32) -> Vec<PassTiming> {
+    let mask = if valid_bits >= 64 { u64::MAX } else { (1u64 << valid_bits) - 1 };
+    passes.iter().zip(ticks.chunks_exact(2))
+        .map(|(sample, t)| PassTiming {
+            sample: sample.clone(),
+            start: (t[0] & mask) as f64 * period / 1000.0,
+            duration: (t[1].wrapping_sub(t[0]) & mask) as f64 * period / 1000.0,
+        })
+        .collect()
+}
+
+/*
+ * Chrome trace JSON of @events, viewable in chrome://tracing or Perfetto
+ */
+pub fn chrome_trace(events: &[PassTiming]) -> String {
+    let origin = events.iter().map(|e| e.start).fold(f64::INFINITY, f64::min);
+    let events = events.iter()
+        .map(|e| format!(
+            "{{\"name\":\"{}\",\"cat\":\"gpu\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":1,\"args\":{{\"dispatch\":\"{}x{}x{}\",\"memory\":{}}}}}",
+            escape_json(&e.sample.name), e.start - origin, e.duration,
+            e.sample.dispatch[0], e.sample.dispatch[1], e.sample.dispatch[2], e.sample.memory
+        ))
+        .collect::<Vec<String>>();
+    format!("{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n", events.join(",\n"))
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.2} GiB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+/**
+ *  Egui window with the GPU time of each pass, averaged over the last frames.
+ */
+pub struct Profiler {
+    pub open: bool,
+    /// Durations of the last frames per pass, in pass order
+    history: Vec<(PassSample, VecDeque<f64>)>,
+    recording: bool,
+    trace: Vec<PassTiming>,
+    path: String,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Self {
+            open: false,
+            history: Vec::new(),
+            recording: false,
+            trace: Vec::new(),
+            path: "trace.json".to_string(),
+        }
+    }
+
+    /*
+     * Add the timings of a rendered frame
+     */
+    pub fn add_frame(&mut self, timings: Vec<PassTiming>) {
+        // Passes that no longer run are dropped
+        let mut history = std::mem::take(&mut self.history);
+        for timing in &timings {
+            let mut durations = history.iter()
+                .position(|(s, _)| s.name == timing.sample.name)
+                .map(|i| history.swap_remove(i).1)
+                .unwrap_or_default();
+            durations.push_back(timing.duration);
+            while durations.len() > AVERAGE_FRAMES {
+                durations.pop_front();
+            }
+            self.history.push((timing.sample.clone(), durations));
+        }
+
+        if self.recording {
+            if self.trace.len() + timings.len() > MAX_TRACE_EVENTS {
+                info!("Trace is full, stopped recording");
+                self.recording = false;
+            } else {
+                self.trace.extend(timings);
+            }
+        }
+    }
+
+    /*
+     * Average duration of each pass in microseconds
+     */
+    pub fn averages(&self) -> Vec<(&PassSample, f64)> {
+        self.history.iter()
+            .map(|(sample, durations)| (sample, durations.iter().sum::<f64>() / durations.len().max(1) as f64))
+            .collect()
+    }
+
+    pub fn show(&mut self, context: &Context, image_memory: u64) {
+        let mut open = self.open;
+        egui::Window::new("Profiler")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(context, |ui| {
+                self.table(ui);
+                ui.separator();
+                let total = self.averages().iter().map(|(_, d)| d).sum::<f64>();
+                ui.label(format!("GPU time: {:.3} ms, image memory: {}", total / 1000.0, format_bytes(image_memory)));
+                ui.separator();
+                self.trace_controls(ui);
+            });
+        self.open = open;
+    }
+
+    fn table(&self, ui: &mut Ui) {
+        if self.history.is_empty() {
+            ui.label("No timings yet, the device may not support timestamps");
+            return;
+        }
+
+        Grid::new("profiler").striped(true).num_columns(4).show(ui, |ui| {
+            ui.strong("Pass");
+            ui.strong("GPU time");
+            ui.strong("Dispatch");
+            ui.strong("Memory");
+            ui.end_row();
+            for (sample, average) in self.averages() {
+                ui.label(&sample.name);
+                ui.monospace(format!("{:.3} ms", average / 1000.0));
+                ui.monospace(format!("{}x{}x{}", sample.dispatch[0], sample.dispatch[1], sample.dispatch[2]));
+                ui.monospace(format_bytes(sample.memory));
+                ui.end_row();
+            }
+        });
+    }
+
+    fn trace_controls(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let label = if self.recording { "Stop" } else { "Record trace" };
+            if ui.button(label).clicked() {
+                if !self.recording {
+                    self.trace.clear();
+                }
+                self.recording = !self.recording;
+            }
+            ui.label(format!("{} events", self.trace.len()));
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.path).desired_width(200.0));
+            if ui.add_enabled(!self.trace.is_empty(), egui::Button::new("Save trace")).clicked() {
+                match fs::write(&self.path, chrome_trace(&self.trace)) {
+                    Ok(()) => info!("Saved trace to {}", self.path),
+                    Err(e) => error!("Failed to save trace: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn sample(name: &str) -> PassSample {
+        PassSample { name: name.to_string(), dispatch: [8, 4, 1], memory: 1024 }
+    }
+
+    fn timing(name: &str, start: f64, duration: f64) -> PassTiming {
+        PassTiming { sample: sample(name), start, duration }
+    }
+
+    #[test]
+    fn ticks_are_scaled_to_microseconds() {
+        let timings = timings(&[sample("a"), sample("b")], &[1000, 3000, 3000, 3500], 2.0, 64);
+        assert_eq!(timings.len(), 2);
+        assert_eq!((timings[0].start, timings[0].duration), (2.0, 4.0));
+        assert_eq!((timings[1].start, timings[1].duration), (6.0, 1.0));
+        assert_eq!(timings[1].sample.name, "b");
+    }
+
+    #[test]
+    fn ticks_are_masked_to_the_valid_bits() {
+        // Bits above the valid ones are undefined
+        let timings = timings(&[sample("a")], &[0xdead_0000_0000_0010, 0xbeef_0000_0000_0030], 1000.0, 36);
+        assert_eq!((timings[0].start, timings[0].duration), (16.0, 32.0));
+    }
+
+    #[test]
+    fn ticks_wrap_around_at_the_valid_bits() {
+        let timings = timings(&[sample("a")], &[0xffff_fff0, 0x10], 1000.0, 32);
+        assert_eq!(timings[0].duration, 32.0);
+    }
+
+    #[test]
+    fn missing_ticks_drop_passes() {
+        assert_eq!(timings(&[sample("a"), sample("b")], &[0, 10, 20], 1.0, 64).len(), 1);
+    }
+
+    #[test]
+    fn json_is_escaped() {
+        assert_eq!(escape_json("plain pass"), "plain pass");
+        assert_eq!(escape_json("\"quoted\" \\ path"), "\\\"quoted\\\" \\\\ path");
+        assert_eq!(escape_json("a\nb\tc\u{1}"), "a\\u000ab\\u0009c\\u0001");
+        assert_eq!(escape_json("blur ✓"), "blur ✓");
+    }
+
+    #[test]
+    fn chrome_trace_parses() {
+        let trace: Value = serde_json::from_str(&chrome_trace(&[
+            timing("scene", 100.0, 20.5),
+            timing("\"blur\"\n\\ pass", 125.0, 10.0),
+        ])).unwrap();
+        assert_eq!(trace["displayTimeUnit"], "ms");
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+
+        let first = &events[0];
+        assert_eq!(first["name"], "scene");
+        assert_eq!(first["ph"], "X");
+        assert_eq!(first["ts"], 0.0);
+        assert_eq!(first["dur"], 20.5);
+        assert_eq!(first["args"]["dispatch"], "8x4x1");
+        assert_eq!(first["args"]["memory"], 1024);
+
+        // Timestamps are relative to the first event
+        assert_eq!(events[1]["name"], "\"blur\"\n\\ pass");
+        assert_eq!(events[1]["ts"], 25.0);
+    }
+
+    #[test]
+    fn empty_chrome_trace_parses() {
+        let trace: Value = serde_json::from_str(&chrome_trace(&[])).unwrap();
+        assert_eq!(trace["traceEvents"], Value::Array(Vec::new()));
+    }
+}
@@ -0,0 +1,267 @@
+use ash::vk;
+use half::f16;
+use crate::Error;
+
+/**
+ *  Layout of pixels read back from a GPU image.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PixelFormat {
+    Rgba8Unorm,
+    Bgra8Unorm,
+    Rgba8Srgb,
+    Bgra8Srgb,
+    Rgba16Float,
+    Rgba32Float,
+}
+
+impl PixelFormat {
+    pub fn from_vk(format: vk::Format) -> Result<PixelFormat, Error> {
+        match format {
+            vk::Format::R8G8B8A8_UNORM => Ok(PixelFormat::Rgba8Unorm),
+            vk::Format::B8G8R8A8_UNORM => Ok(PixelFormat::Bgra8Unorm),
+            vk::Format::R8G8B8A8_SRGB => Ok(PixelFormat::Rgba8Srgb),
+            vk::Format::B8G8R8A8_SRGB => Ok(PixelFormat::Bgra8Srgb),
+            vk::Format::R16G16B16A16_SFLOAT => Ok(PixelFormat::Rgba16Float),
+            vk::Format::R32G32B32A32_SFLOAT => Ok(PixelFormat::Rgba32Float),
+            f => Err(Error::Encode(format!("Unsupported image format for readback: {:?}", f))),
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8Unorm | PixelFormat::Bgra8Unorm | PixelFormat::Rgba8Srgb | PixelFormat::Bgra8Srgb => 4,
+            PixelFormat::Rgba16Float => 8,
+            PixelFormat::Rgba32Float => 16,
+        }
+    }
+}
+
+/**
+ *  Mapping of linear values above 1 into the displayable range.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tonemap {
+    Clamp,
+    Reinhard,
+    /// Narkowicz' fit of the ACES filmic curve
+    Aces,
+}
+
+impl Tonemap {
+    pub const ALL: [Tonemap; 3] = [Tonemap::Clamp, Tonemap::Reinhard, Tonemap::Aces];
+
+    pub fn apply(&self, v: f32) -> f32 {
+        let v = v.max(0.0);
+        match self {
+            Tonemap::Clamp => v.min(1.0),
+            Tonemap::Reinhard => v / (1.0 + v),
+            Tonemap::Aces => ((v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14)).clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tonemap::Clamp => "Clamp",
+            Tonemap::Reinhard => "Reinhard",
+            Tonemap::Aces => "ACES",
+        }
+    }
+}
+
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+/*
+ * Convert @data, @height rows of @row_pitch bytes in @format, to linear RGBA floats.
+ * UNORM and float formats hold linear values as they are, sRGB formats are decoded.
+ */
+pub fn decode_pixels(data: &[u8], width: u32, height: u32, row_pitch: usize, format: PixelFormat) -> Result<Vec<f32>, Error> {
+    let row_size = width as usize * format.bytes_per_pixel();
+    if row_pitch < row_size || data.len() < row_pitch * (height as usize).saturating_sub(1) + row_size {
+        return Err(Error::Encode(format!("{} bytes of readback data are too few for a {}x{} {:?} image", data.len(), width, height, format)));
+    }
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in data.chunks(row_pitch).take(height as usize) {
+        let row = &row[..row_size];
+        match format {
+            PixelFormat::Rgba8Unorm => row.chunks_exact(4)
+                .for_each(|p| pixels.extend([p[0], p[1], p[2], p[3]].map(|c| c as f32 / 255.0))),
+            PixelFormat::Bgra8Unorm => row.chunks_exact(4)
+                .for_each(|p| pixels.extend([p[2], p[1], p[0], p[3]].map(|c| c as f32 / 255.0))),
+            PixelFormat::Rgba8Srgb => row.chunks_exact(4)
+                .for_each(|p| pixels.extend(decode_srgb8([p[0], p[1], p[2], p[3]]))),
+            PixelFormat::Bgra8Srgb => row.chunks_exact(4)
+                .for_each(|p| pixels.extend(decode_srgb8([p[2], p[1], p[0], p[3]]))),
+            PixelFormat::Rgba16Float => row.chunks_exact(2)
+                .for_each(|c| pixels.push(f16::from_le_bytes([c[0], c[1]]).to_f32())),
+            PixelFormat::Rgba32Float => row.chunks_exact(4)
+                .for_each(|c| pixels.push(f32::from_le_bytes([c[0], c[1], c[2], c[3]]))),
+        }
+    }
+
+    Ok(pixels)
+}
+
+/*
+ * Alpha is stored linearly, only the color channels are sRGB encoded
+ */
+fn decode_srgb8(rgba: [u8; 4]) -> [f32; 4] {
+    let [r, g, b, a] = rgba.map(|c| c as f32 / 255.0);
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+/*
+ * Convert linear RGBA floats to display values in [0, 1] for integer image formats
+ */
+pub fn encode_display(data: &mut [f32], tonemap: Tonemap) {
+    for pixel in data.chunks_exact_mut(4) {
+        for c in &mut pixel[..3] {
+            *c = linear_to_srgb(tonemap.apply(*c));
+        }
+        pixel[3] = pixel[3].clamp(0.0, 1.0);
+    }
+}
+
+pub fn to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+pub fn to_u16(v: f32) -> u16 {
+    (v.clamp(0.0, 1.0) * 65535.0).round() as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn all_close(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| close(*a, *b))
+    }
+
+    #[test]
+    fn unorm_is_read_as_stored() {
+        let pixels = decode_pixels(&[0, 51, 255, 128], 1, 1, 4, PixelFormat::Rgba8Unorm).unwrap();
+        assert!(all_close(&pixels, &[0.0, 0.2, 1.0, 128.0 / 255.0]));
+    }
+
+    #[test]
+    fn bgra_is_swizzled() {
+        let data = [10, 20, 30, 40];
+        let unorm = decode_pixels(&data, 1, 1, 4, PixelFormat::Bgra8Unorm).unwrap();
+        assert!(all_close(&unorm, &[30.0 / 255.0, 20.0 / 255.0, 10.0 / 255.0, 40.0 / 255.0]));
+
+        let srgb = decode_pixels(&data, 1, 1, 4, PixelFormat::Bgra8Srgb).unwrap();
+        let rgb = decode_pixels(&[30, 20, 10, 40], 1, 1, 4, PixelFormat::Rgba8Srgb).unwrap();
+        assert_eq!(srgb, rgb);
+    }
+
+    #[test]
+    fn srgb_color_is_decoded_and_alpha_is_not() {
+        let pixels = decode_pixels(&[188, 0, 255, 188], 1, 1, 4, PixelFormat::Rgba8Srgb).unwrap();
+        assert!(close(pixels[0], srgb_to_linear(188.0 / 255.0)));
+        assert!(close(pixels[0], 0.5029));
+        assert_eq!(pixels[1], 0.0);
+        assert_eq!(pixels[2], 1.0);
+        assert!(close(pixels[3], 188.0 / 255.0));
+    }
+
+    #[test]
+    fn floats_are_read_as_stored() {
+        let values = [-1.5f32, 0.25, 4.0, 1.0];
+        let half = values.iter().flat_map(|v| f16::from_f32(*v).to_le_bytes()).collect::<Vec<u8>>();
+        assert_eq!(decode_pixels(&half, 1, 1, 8, PixelFormat::Rgba16Float).unwrap(), values);
+
+        let single = values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        assert_eq!(decode_pixels(&single, 1, 1, 16, PixelFormat::Rgba32Float).unwrap(), values);
+    }
+
+    #[test]
+    fn row_padding_is_skipped() {
+        // 2x2 image with rows padded from 8 to 12 bytes, the last row needs no padding
+        let data = [
+            1, 2, 3, 4, 5, 6, 7, 8, 99, 99, 99, 99,
+            9, 10, 11, 12, 13, 14, 15, 16,
+        ];
+        let pixels = decode_pixels(&data, 2, 2, 12, PixelFormat::Rgba8Unorm).unwrap();
+        let expected = (1..=16).map(|v| v as f32 / 255.0).collect::<Vec<f32>>();
+        assert!(all_close(&pixels, &expected));
+    }
+
+    #[test]
+    fn short_data_is_an_error() {
+        assert!(decode_pixels(&[0; 15], 2, 2, 8, PixelFormat::Rgba8Unorm).is_err());
+        assert!(decode_pixels(&[0; 16], 2, 2, 4, PixelFormat::Rgba8Unorm).is_err());
+        assert!(decode_pixels(&[0; 16], 1, 1, 16, PixelFormat::Rgba16Float).is_ok());
+    }
+
+    #[test]
+    fn formats_map_from_vulkan() {
+        assert_eq!(PixelFormat::from_vk(vk::Format::R8G8B8A8_UNORM).unwrap(), PixelFormat::Rgba8Unorm);
+        assert_eq!(PixelFormat::from_vk(vk::Format::B8G8R8A8_SRGB).unwrap(), PixelFormat::Bgra8Srgb);
+        assert_eq!(PixelFormat::from_vk(vk::Format::R16G16B16A16_SFLOAT).unwrap().bytes_per_pixel(), 8);
+        assert_eq!(PixelFormat::from_vk(vk::Format::R32G32B32A32_SFLOAT).unwrap().bytes_per_pixel(), 16);
+        assert!(PixelFormat::from_vk(vk::Format::R8_UNORM).is_err());
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=100 {
+            let v = i as f32 / 100.0;
+            assert!(close(linear_to_srgb(srgb_to_linear(v)), v));
+            assert!(close(srgb_to_linear(linear_to_srgb(v)), v));
+        }
+        assert!(close(linear_to_srgb(0.5), 0.7354));
+        assert!(close(srgb_to_linear(0.04), 0.04 / 12.92));
+    }
+
+    #[test]
+    fn tonemap_maps_into_the_display_range() {
+        assert_eq!(Tonemap::Clamp.apply(0.5), 0.5);
+        assert_eq!(Tonemap::Clamp.apply(4.0), 1.0);
+        assert!(close(Tonemap::Reinhard.apply(1.0), 0.5));
+        assert!(close(Tonemap::Reinhard.apply(3.0), 0.75));
+        assert!(close(Tonemap::Aces.apply(1.0), 2.54 / 3.16));
+        assert_eq!(Tonemap::Aces.apply(1000.0), 1.0);
+        for tonemap in Tonemap::ALL {
+            assert_eq!(tonemap.apply(0.0), 0.0);
+            assert_eq!(tonemap.apply(-2.0), 0.0);
+            assert!(tonemap.apply(0.2) < tonemap.apply(0.8));
+        }
+    }
+
+    #[test]
+    fn display_encoding_leaves_alpha_linear() {
+        let mut pixels = [0.5, 2.0, -1.0, 1.5];
+        encode_display(&mut pixels, Tonemap::Clamp);
+        assert!(close(pixels[0], linear_to_srgb(0.5)));
+        assert!(all_close(&pixels[1..], &[1.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn integers_round_and_clamp() {
+        assert_eq!(to_u8(0.0), 0);
+        assert_eq!(to_u8(1.0), 255);
+        assert_eq!(to_u8(0.5), 128);
+        assert_eq!(to_u8(0.499 / 255.0), 0);
+        assert_eq!(to_u8(0.501 / 255.0), 1);
+        assert_eq!(to_u8(-1.0), 0);
+        assert_eq!(to_u8(2.0), 255);
+        assert_eq!(to_u8(f32::NAN), 0);
+
+        assert_eq!(to_u16(1.0), 65535);
+        assert_eq!(to_u16(0.5), 32768);
+        assert_eq!(to_u16(-1.0), 0);
+        assert_eq!(to_u16(7.0), 65535);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::app::draw_orch::{DrawConfig, ImageConfig};
+
+/**
+ *  What changes when switching from one DrawConfig to another while running.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigDiff {
+    /// For each new image, the index of the previous image whose contents it keeps
+    pub matches: Vec<Option<usize>>,
+    /// The amount of images changed, so pipelines using the previous descriptor layout have to be rebuilt
+    pub layout_changed: bool,
+    /// The amount of animated parameters changed, so the parameter buffers have to be resized
+    pub parameters_changed: bool,
+}
+
+impl ConfigDiff {
+    /*
+     * Differences of @next to @previous. A different image format replaces all images.
+     */
+    pub fn new(previous: &DrawConfig, next: &DrawConfig) -> ConfigDiff {
+        let matches = if next.image_format == previous.image_format {
+            matching_images(&previous.images, &next.images)
+        } else {
+            vec![None; next.images.len()]
+        };
+        Self {
+            matches,
+            layout_changed: next.images.len() != previous.images.len(),
+            parameters_changed: next.animation.tracks.len() != previous.animation.tracks.len(),
+        }
+    }
+
+    /*
+     * Configs of the new images that don't keep a previous image, out of @images
+     */
+    pub fn added(&self, images: &[ImageConfig]) -> Vec<ImageConfig> {
+        images.iter().zip(&self.matches)
+            .filter(|(_, m)| m.is_none())
+            .map(|(c, _)| c.clone())
+            .collect()
+    }
+
+    pub fn kept(&self) -> usize {
+        self.matches.iter().flatten().count()
+    }
+
+    /*
+     * Resources of the new images: the @previous resource of each matched image, the @added ones in order for the
+     * others. Previous resources without a match are dropped.
+     */
+    pub fn arrange<T>(&self, previous: Vec<T>, added: Vec<T>) -> Vec<T> {
+        let mut previous = previous.into_iter().map(Some).collect::<Vec<Option<T>>>();
+        let mut added = added.into_iter();
+        self.matches.iter()
+            .map(|m| match m {
+                Some(index) => previous[*index].take().expect("Previous image matched twice"),
+                None => added.next().expect("Fewer added images than unmatched ones"),
+            })
+            .collect()
+    }
+}
+
+/*
+ * For each of @images, the index of the image in @previous that it replaces. Named images replace the previous image
+ * with their name, unnamed ones the unnamed image at their index.
+ */
+pub fn matching_images(previous: &[ImageConfig], images: &[ImageConfig]) -> Vec<Option<usize>> {
+    images.iter().enumerate()
+        .map(|(i, image)| match &image.name {
+            Some(name) => previous.iter().position(|p| p.name.as_ref() == Some(name)),
+            None => previous.get(i).filter(|p| p.name.is_none()).map(|_| i),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::image::ImageFormat;
+    use crate::app::keyframes::{Animation, Track};
+
+    fn named(names: &[&str]) -> Vec<ImageConfig> {
+        names.iter().map(|n| ImageConfig::new(n)).collect()
+    }
+
+    fn unnamed(count: usize) -> Vec<ImageConfig> {
+        vec![ImageConfig::default(); count]
+    }
+
+    fn config(images: Vec<ImageConfig>) -> DrawConfig {
+        DrawConfig::builder().images(images).build()
+    }
+
+    #[test]
+    fn named_images_match_by_name() {
+        let previous = named(&["scene", "blur", "output"]);
+        assert_eq!(matching_images(&previous, &previous), vec![Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn reordered_images_keep_their_contents() {
+        let previous = named(&["scene", "blur", "output"]);
+        assert_eq!(matching_images(&previous, &named(&["output", "scene", "blur"])), vec![Some(2), Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn added_and_removed_images() {
+        let previous = named(&["scene", "blur", "output"]);
+        assert_eq!(matching_images(&previous, &named(&["scene", "glow", "output"])), vec![Some(0), None, Some(2)]);
+        assert_eq!(matching_images(&previous, &named(&["output"])), vec![Some(2)]);
+        assert_eq!(matching_images(&[], &named(&["scene"])), vec![None]);
+    }
+
+    #[test]
+    fn unnamed_images_match_by_index() {
+        assert_eq!(matching_images(&unnamed(2), &unnamed(3)), vec![Some(0), Some(1), None]);
+        assert_eq!(matching_images(&unnamed(3), &unnamed(1)), vec![Some(0)]);
+    }
+
+    #[test]
+    fn named_and_unnamed_images_dont_match() {
+        let mut previous = unnamed(1);
+        previous.extend(named(&["output"]));
+        let mut images = named(&["scene"]);
+        images.extend(unnamed(1));
+        assert_eq!(matching_images(&previous, &images), vec![None, None]);
+    }
+
+    #[test]
+    fn diff_of_images() {
+        let diff = ConfigDiff::new(&config(named(&["a", "b"])), &config(named(&["b", "c", "a"])));
+        assert_eq!(diff.matches, vec![Some(1), None, Some(0)]);
+        assert_eq!(diff.kept(), 2);
+        assert!(diff.layout_changed);
+        assert!(!diff.parameters_changed);
+        assert_eq!(diff.added(&named(&["b", "c", "a"])).iter().map(|c| c.name.clone().unwrap()).collect::<Vec<String>>(), vec!["c"]);
+    }
+
+    #[test]
+    fn format_change_replaces_all_images() {
+        let previous = config(named(&["a", "b"]));
+        let next = DrawConfig::builder().images(named(&["a", "b"])).image_format(ImageFormat::Rgba16Float).build();
+        let diff = ConfigDiff::new(&previous, &next);
+        assert_eq!(diff.matches, vec![None, None]);
+        assert!(!diff.layout_changed);
+    }
+
+    #[test]
+    fn parameter_count_changes() {
+        let mut animation = Animation::default();
+        animation.tracks.push(Track::new("speed"));
+        let next = DrawConfig::builder().images(named(&["a"])).animation(animation).build();
+        assert!(ConfigDiff::new(&config(named(&["a"])), &next).parameters_changed);
+    }
+
+    #[test]
+    fn resources_are_arranged_by_match() {
+        let diff = ConfigDiff::new(&config(named(&["a", "b", "c"])), &config(named(&["c", "d", "a", "e"])));
+        let arranged = diff.arrange(vec!["old a", "old b", "old c"], vec!["new d", "new e"]);
+        assert_eq!(arranged, vec!["old c", "new d", "old a", "new e"]);
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::{HashMap, HashSet};
+use rspirv::dr::{Instruction, Operand};
+use rspirv::spirv::{Decoration, Dim, ImageFormat as SpirvFormat, Op, StorageClass, Word};
+use crate::app::image::ImageFormat;
+use crate::Error;
+
+/**
+ *  Type of a push constant field as seen by shaders.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FieldType {
+    Float,
+    Int,
+    Vec2,
+    IntArray(u32),
+}
+
+/**
+ *  A field of the push constants kiyo provides, at a byte offset in the push constant block.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PushConstantField {
+    pub name: &'static str,
+    pub offset: u32,
+    pub ty: FieldType,
+}
+
+/**
+ *  Resources kiyo binds for every pass: the storage images at binding 0, the parameter buffer at binding 1 and
+ *  the push constants.
+ */
+pub struct ShaderInterface<'a> {
+    pub image_count: u32,
+    pub image_format: ImageFormat,
+    pub push_constants: &'a [PushConstantField],
+    pub push_constant_size: u32,
+}
+
+enum SpirvType {
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: Word, count: u32 },
+    Image { dim: Dim, storage: bool, format: SpirvFormat },
+    /// Arrays sized by a specialization constant have no known length
+    Array { element: Word, length: Option<u32> },
+    RuntimeArray { element: Word },
+    Struct { members: Vec<Word> },
+    Pointer { pointee: Word },
+}
+
+/**
+ *  Declarations of a SPIR-V module, without its functions.
+ */
+#[derive(Default)]
+struct Reflection {
+    types: HashMap<Word, SpirvType>,
+    constants: HashMap<Word, u32>,
+    names: HashMap<Word, String>,
+    member_names: HashMap<(Word, u32), String>,
+    sets: HashMap<Word, u32>,
+    bindings: HashMap<Word, u32>,
+    offsets: HashMap<(Word, u32), u32>,
+    strides: HashMap<Word, u32>,
+    buffer_blocks: HashSet<Word>,
+    /// Global variables as (id, pointer type, storage class)
+    variables: Vec<(Word, Word, StorageClass)>,
+}
+
+/*
+ * Reflect the compute shader in @code and check its resources against @interface.
+ * Returns all mismatches at once, one per line.
+ */
+pub fn check_interface(code: &[u32], interface: &ShaderInterface) -> Result<(), Error> {
+    let module = rspirv::dr::load_words(code)
+        .map_err(|e| Error::Shader(format!("Failed to reflect SPIR-V: {}", e)))?;
+    let reflection = Reflection::new(&module);
+
+    let errors = reflection.interface_errors(interface);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Shader(errors.join("\n")))
+    }
+}
+
+fn word(operand: Option<&Operand>) -> Option<u32> {
+    match operand {
+        Some(Operand::IdRef(id)) | Some(Operand::LiteralBit32(id)) => Some(*id),
+        _ => None,
+    }
+}
+
+impl Reflection {
+    fn new(module: &rspirv::dr::Module) -> Reflection {
+        let mut reflection = Reflection::default();
+        for instruction in &module.debug_names {
+            reflection.add_name(instruction);
+        }
+        for instruction in &module.annotations {
+            reflection.add_decoration(instruction);
+        }
+        for instruction in &module.types_global_values {
+            reflection.add_declaration(instruction);
+        }
+        reflection
+    }
+
+    fn add_name(&mut self, instruction: &Instruction) {
+        match (instruction.class.opcode, instruction.operands.as_slice()) {
+            (Op::Name, [Operand::IdRef(id), Operand::LiteralString(name)]) => {
+                self.names.insert(*id, name.clone());
+            },
+            (Op::MemberName, [Operand::IdRef(id), Operand::LiteralBit32(member), Operand::LiteralString(name)]) => {
+                self.member_names.insert((*id, *member), name.clone());
+            },
+            _ => {},
+        }
+    }
+
+    fn add_decoration(&mut self, instruction: &Instruction) {
+        match (instruction.class.opcode, instruction.operands.as_slice()) {
+            (Op::Decorate, [Operand::IdRef(id), Operand::Decoration(decoration), rest @ ..]) => {
+                let value = word(rest.first());
+                match decoration {
+                    Decoration::DescriptorSet => { self.sets.insert(*id, value.unwrap_or(0)); },
+                    Decoration::Binding => { self.bindings.insert(*id, value.unwrap_or(0)); },
+                    Decoration::ArrayStride => { self.strides.insert(*id, value.unwrap_or(0)); },
+                    Decoration::BufferBlock => { self.buffer_blocks.insert(*id); },
+                    _ => {},
+                }
+            },
+            (Op::MemberDecorate, [Operand::IdRef(id), Operand::LiteralBit32(member), Operand::Decoration(Decoration::Offset), Operand::LiteralBit32(offset)]) => {
+                self.offsets.insert((*id, *member), *offset);
+            },
+            _ => {},
+        }
+    }
+
+    fn add_declaration(&mut self, instruction: &Instruction) {
+        let Some(id) = instruction.result_id else {
+            return;
+        };
+        let operands = instruction.operands.as_slice();
+        let ty = match (instruction.class.opcode, operands) {
+            (Op::TypeInt, [Operand::LiteralBit32(width), Operand::LiteralBit32(signed)]) => SpirvType::Int { width: *width, signed: *signed != 0 },
+            (Op::TypeFloat, [Operand::LiteralBit32(width), ..]) => SpirvType::Float { width: *width },
+            (Op::TypeVector, [Operand::IdRef(component), Operand::LiteralBit32(count)]) => SpirvType::Vector { component: *component, count: *count },
+            (Op::TypeImage, [_, Operand::Dim(dim), _, _, _, Operand::LiteralBit32(sampled), Operand::ImageFormat(format), ..]) => {
+                SpirvType::Image { dim: *dim, storage: *sampled == 2, format: *format }
+            },
+            (Op::TypeArray, [Operand::IdRef(element), Operand::IdRef(length)]) => SpirvType::Array { element: *element, length: self.constants.get(length).copied() },
+            (Op::TypeRuntimeArray, [Operand::IdRef(element)]) => SpirvType::RuntimeArray { element: *element },
+            (Op::TypeStruct, members) => SpirvType::Struct { members: members.iter().filter_map(|m| word(Some(m))).collect() },
+            (Op::TypePointer, [_, Operand::IdRef(pointee)]) => SpirvType::Pointer { pointee: *pointee },
+            (Op::Constant, [Operand::LiteralBit32(value)]) => {
+                self.constants.insert(id, *value);
+                return;
+            },
+            (Op::Variable, [Operand::StorageClass(class), ..]) => {
+                if let Some(pointer) = instruction.result_type {
+                    self.variables.push((id, pointer, *class));
+                }
+                return;
+            },
+            _ => return,
+        };
+        self.types.insert(id, ty);
+    }
+
+    fn name(&self, id: Word) -> &str {
+        self.names.get(&id).map(|n| n.as_str()).unwrap_or("<unnamed>")
+    }
+
+    fn pointee(&self, pointer: Word) -> Word {
+        match self.types.get(&pointer) {
+            Some(SpirvType::Pointer { pointee }) => *pointee,
+            _ => pointer,
+        }
+    }
+
+    /*
+     * Size in bytes of the type @id, arrays without a known length count as empty
+     */
+    fn size(&self, id: Word) -> u32 {
+        match self.types.get(&id) {
+            Some(SpirvType::Int { width, .. }) | Some(SpirvType::Float { width }) => width / 8,
+            Some(SpirvType::Vector { component, count }) => self.size(*component) * count,
+            Some(SpirvType::Array { element, length }) => {
+                let stride = self.strides.get(&id).copied().unwrap_or_else(|| self.size(*element));
+                stride * length.unwrap_or(0)
+            },
+            Some(SpirvType::Struct { members }) => members.iter().enumerate()
+                .map(|(i, m)| self.offsets.get(&(id, i as u32)).copied().unwrap_or(0) + self.size(*m))
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn is_scalar(&self, id: Word, float: bool) -> bool {
+        match self.types.get(&id) {
+            Some(SpirvType::Float { width }) => float && *width == 32,
+            Some(SpirvType::Int { width, signed }) => !float && *signed && *width == 32,
+            _ => false,
+        }
+    }
+
+    fn interface_errors(&self, interface: &ShaderInterface) -> Vec<String> {
+        let mut errors = Vec::new();
+        for &(id, pointer, class) in &self.variables {
+            let name = self.name(id);
+            let ty = self.pointee(pointer);
+            match class {
+                StorageClass::PushConstant => errors.extend(self.push_constant_errors(ty, interface)),
+                StorageClass::UniformConstant | StorageClass::Uniform | StorageClass::StorageBuffer => {
+                    let set = self.sets.get(&id).copied().unwrap_or(0);
+                    if set != 0 {
+                        errors.push(format!("`{}` uses descriptor set {}, kiyo only provides set 0", name, set));
+                        continue;
+                    }
+
+                    let Some(&binding) = self.bindings.get(&id) else {
+                        errors.push(format!("`{}` has no binding, kiyo provides binding 0 (images) and 1 (parameters)", name));
+                        continue;
+                    };
+                    match binding {
+                        0 => errors.extend(self.image_errors(name, ty, interface)),
+                        1 => {
+                            let storage = class == StorageClass::StorageBuffer
+                                || (class == StorageClass::Uniform && self.buffer_blocks.contains(&ty));
+                            if !storage {
+                                errors.push(format!("`{}` at binding 1 must be a storage buffer, it holds the animated parameters", name));
+                            }
+                        },
+                        b => errors.push(format!("`{}` uses binding {}, kiyo only provides binding 0 (images) and 1 (parameters)", name, b)),
+                    }
+                },
+                _ => {},
+            }
+        }
+        errors
+    }
+
+    fn image_errors(&self, name: &str, ty: Word, interface: &ShaderInterface) -> Vec<String> {
+        let mut errors = Vec::new();
+        let image_count = interface.image_count;
+        let (element, count) = match self.types.get(&ty) {
+            Some(SpirvType::Array { element, length }) => (*element, length.unwrap_or(image_count)),
+            Some(SpirvType::RuntimeArray { element }) => (*element, image_count),
+            _ => (ty, 1),
+        };
+
+        if count > image_count {
+            errors.push(format!("`{}` declares {} images, but only {} are provided, use NUM_IMAGES as its size", name, count, image_count));
+        }
+        match self.types.get(&element) {
+            Some(SpirvType::Image { dim: Dim::Dim2D, storage: true, format }) => {
+                if *format != spirv_format(interface.image_format) && *format != SpirvFormat::Unknown {
+                    errors.push(format!("`{}` has format {:?}, but the images are {}, use IMAGE_FORMAT as its format", name, format, interface.image_format.glsl()));
+                }
+            },
+            _ => errors.push(format!("`{}` at binding 0 must be an array of 2D storage images", name)),
+        }
+        errors
+    }
+
+    /*
+     * Members of the struct @ty as (name, offset, type), nested structs are flattened into their members.
+     * Some compilers wrap the push constant block into another struct.
+     */
+    fn members(&self, ty: Word, members: &[Word], base: u32) -> Vec<(&str, u32, Word)> {
+        members.iter().enumerate().flat_map(|(i, &member)| {
+            let offset = base + self.offsets.get(&(ty, i as u32)).copied().unwrap_or(0);
+            match self.types.get(&member) {
+                Some(SpirvType::Struct { members }) => self.members(member, members, offset),
+                _ => vec![(self.member_names.get(&(ty, i as u32)).map(|n| n.as_str()).unwrap_or("<unnamed>"), offset, member)],
+            }
+        }).collect()
+    }
+
+    fn push_constant_errors(&self, ty: Word, interface: &ShaderInterface) -> Vec<String> {
+        let mut errors = Vec::new();
+        let Some(SpirvType::Struct { members }) = self.types.get(&ty) else {
+            errors.push("The push constants must be a block".to_string());
+            return errors;
+        };
+
+        let size = self.size(ty);
+        if size > interface.push_constant_size {
+            errors.push(format!("The push constant block is {} bytes, but kiyo provides {}", size, interface.push_constant_size));
+        }
+
+        for (name, offset, member) in self.members(ty, members, 0) {
+            let Some(field) = interface.push_constants.iter().find(|f| f.offset == offset) else {
+                errors.push(format!("Push constant `{}` at offset {} doesn't line up with any of kiyo's push constants", name, offset));
+                continue;
+            };
+
+            let matches = match (field.ty, self.types.get(&member)) {
+                (FieldType::Float, _) => self.is_scalar(member, true),
+                (FieldType::Int, _) => self.is_scalar(member, false),
+                (FieldType::Vec2, Some(SpirvType::Vector { component, count })) => *count == 2 && self.is_scalar(*component, true),
+                (FieldType::IntArray(length), Some(SpirvType::Array { element, length: Some(count) })) => {
+                    *count <= length && self.is_scalar(*element, false)
+                },
+                _ => false,
+            };
+            if !matches {
+                errors.push(format!("Push constant `{}` at offset {} should be {}", name, offset, type_name(field)));
+            }
+        }
+        errors
+    }
+}
+
+fn spirv_format(format: ImageFormat) -> SpirvFormat {
+    match format {
+        ImageFormat::Rgba8 => SpirvFormat::Rgba8,
+        ImageFormat::Rgba16Float => SpirvFormat::Rgba16f,
+        ImageFormat::Rgba32Float => SpirvFormat::Rgba32f,
+    }
+}
+
+fn type_name(field: &PushConstantField) -> String {
+    match field.ty {
+        FieldType::Float => format!("float {}", field.name),
+        FieldType::Int => format!("int {}", field.name),
+        FieldType::Vec2 => format!("vec2 {}", field.name),
+        FieldType::IntArray(length) => format!("int {}[{}]", field.name, length),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use rspirv::binary::Assemble;
+    use crate::app::pipeline::compile_wgsl;
+
+    const FIELDS: [PushConstantField; 6] = [
+        PushConstantField { name: "time", offset: 0, ty: FieldType::Float },
+        PushConstantField { name: "in_image", offset: 4, ty: FieldType::Int },
+        PushConstantField { name: "out_image", offset: 8, ty: FieldType::Int },
+        PushConstantField { name: "_padding", offset: 12, ty: FieldType::Int },
+        PushConstantField { name: "offset", offset: 16, ty: FieldType::Vec2 },
+        PushConstantField { name: "in_images", offset: 24, ty: FieldType::IntArray(4) },
+    ];
+
+    const INTERFACE: ShaderInterface<'static> = ShaderInterface {
+        image_count: 4,
+        image_format: ImageFormat::Rgba8,
+        push_constants: &FIELDS,
+        push_constant_size: 40,
+    };
+
+    /*
+     * Compute shader with @constants as push constant block, @images at binding 0 and @parameters after it
+     */
+    fn shader(constants: &str, images: &str, parameters: &str) -> Vec<u32> {
+        let source = format!("
+            struct Constants {{ {} }};
+            var<push_constant> constants: Constants;
+            @group( 0 ) @binding( 0 ) var images: {};
+            {}
+
+            @compute @workgroup_size( 8, 8, 1 )
+            fn main( @builtin( global_invocation_id ) id: vec3<u32> ) {{
+                let color = vec4<f32>( constants.time );
+                textureStore( images[ 0 ], vec2<i32>( id.xy ), color );
+                textureStore( images[ constants.out_image ], vec2<i32>( id.xy ), color * params[ 0 ] );
+            }}
+        ", constants, images, parameters);
+        compile_wgsl(Path::new("test.wgsl"), &source, &HashMap::new()).unwrap()
+    }
+
+    const CONSTANTS: &str = "time: f32, in_image: i32, out_image: i32";
+    const IMAGES: &str = "binding_array<texture_storage_2d<rgba8unorm, read_write>, 4>";
+    const PARAMETERS: &str = "@group( 0 ) @binding( 1 ) var<storage, read> params: array<f32>;";
+
+    fn errors(code: &[u32], interface: &ShaderInterface) -> String {
+        match check_interface(code, interface) {
+            Err(Error::Shader(errors)) => errors,
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(()) => String::new(),
+        }
+    }
+
+    #[test]
+    fn matching_shader_passes() {
+        assert_eq!(errors(&shader(CONSTANTS, IMAGES, PARAMETERS), &INTERFACE), "");
+        let all = "time: f32, in_image: i32, out_image: i32, padding: i32, offset: vec2<f32>, in_images: array<i32, 4>";
+        assert_eq!(errors(&shader(all, IMAGES, PARAMETERS), &INTERFACE), "");
+    }
+
+    #[test]
+    fn unknown_binding_is_reported() {
+        let parameters = "@group( 0 ) @binding( 2 ) var<storage, read> params: array<f32>;";
+        let errors = errors(&shader(CONSTANTS, IMAGES, parameters), &INTERFACE);
+        assert!(errors.contains("`params` uses binding 2"), "{}", errors);
+    }
+
+    #[test]
+    fn other_descriptor_sets_are_reported() {
+        let parameters = "@group( 1 ) @binding( 1 ) var<storage, read> params: array<f32>;";
+        let errors = errors(&shader(CONSTANTS, IMAGES, parameters), &INTERFACE);
+        assert!(errors.contains("`params` uses descriptor set 1"), "{}", errors);
+    }
+
+    #[test]
+    fn uniform_parameters_are_reported() {
+        let parameters = "@group( 0 ) @binding( 1 ) var<uniform> params: array<vec4<f32>, 4>;";
+        let errors = errors(&shader(CONSTANTS, IMAGES, parameters), &INTERFACE);
+        assert!(errors.contains("`params` at binding 1 must be a storage buffer"), "{}", errors);
+    }
+
+    #[test]
+    fn missing_binding_is_reported() {
+        let mut module = rspirv::dr::load_words(shader(CONSTANTS, IMAGES, PARAMETERS)).unwrap();
+        module.annotations.retain(|i| !matches!(i.operands.get(1), Some(Operand::Decoration(Decoration::Binding))));
+        let errors = errors(&module.assemble(), &INTERFACE);
+        assert!(errors.contains("`images` has no binding"), "{}", errors);
+        assert!(errors.contains("`params` has no binding"), "{}", errors);
+    }
+
+    #[test]
+    fn too_many_images_are_reported() {
+        let images = "binding_array<texture_storage_2d<rgba8unorm, read_write>, 8>";
+        let errors = errors(&shader(CONSTANTS, images, PARAMETERS), &INTERFACE);
+        assert!(errors.contains("`images` declares 8 images, but only 4 are provided"), "{}", errors);
+    }
+
+    #[test]
+    fn image_format_must_match() {
+        let images = "binding_array<texture_storage_2d<rgba16float, read_write>, 4>";
+        let errors = errors(&shader(CONSTANTS, images, PARAMETERS), &INTERFACE);
+        assert!(errors.contains("has format Rgba16f, but the images are rgba8"), "{}", errors);
+
+        let float = ShaderInterface { image_format: ImageFormat::Rgba16Float, ..INTERFACE };
+        assert_eq!(self::errors(&shader(CONSTANTS, images, PARAMETERS), &float), "");
+    }
+
+    #[test]
+    fn push_constant_types_must_match() {
+        let errors = errors(&shader("time: f32, in_image: f32, out_image: i32", IMAGES, PARAMETERS), &INTERFACE);
+        assert_eq!(errors, "Push constant `in_image` at offset 4 should be int in_image");
+
+        let errors = self::errors(&shader(format!("{}, padding: i32, offset: vec3<f32>", CONSTANTS).as_str(), IMAGES, PARAMETERS), &INTERFACE);
+        assert!(errors.contains("Push constant `offset` at offset 16 should be vec2 offset"), "{}", errors);
+    }
+
+    #[test]
+    fn push_constant_offsets_must_line_up() {
+        let constants = format!("{}, padding: i32, offset: f32, misplaced: f32", CONSTANTS);
+        let errors = errors(&shader(&constants, IMAGES, PARAMETERS), &INTERFACE);
+        assert!(errors.contains("Push constant `misplaced` at offset 20 doesn't line up with any of kiyo's push constants"), "{}", errors);
+    }
+
+    #[test]
+    fn push_constants_must_fit() {
+        let constants = format!("{}, padding: i32, offset: vec2<f32>, in_images: array<i32, 8>", CONSTANTS);
+        let errors = errors(&shader(&constants, IMAGES, PARAMETERS), &INTERFACE);
+        assert!(errors.contains("The push constant block is 56 bytes, but kiyo provides 40"), "{}", errors);
+        assert!(errors.contains("should be int in_images[4]"), "{}", errors);
+    }
+}
@@ -0,0 +1,276 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use egui::text::LayoutJob;
+use egui::{Color32, ComboBox, Context, Key, Modifiers, RichText, Ui};
+use egui_extras::syntax_highlighting::{highlight, CodeTheme};
+use log::{error, info};
+use crate::app::effects;
+use crate::app::pipeline::ShaderLanguage;
+use crate::app::shader_errors::ShaderError;
+
+/**
+ *  Side panel to edit the shaders of the passes, saving a shader recompiles it.
+ */
+pub struct ShaderEditor {
+    pub open: bool,
+    /// Path of the shader being edited
+    path: Option<String>,
+    text: String,
+    modified: bool,
+}
+
+impl Default for ShaderEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderEditor {
+    pub fn new() -> ShaderEditor {
+        Self {
+            open: false,
+            path: None,
+            text: String::new(),
+            modified: false,
+        }
+    }
+
+    /*
+     * Show the editor for the shaders of @passes, given as (pass name, shader path), with @errors shown inline.
+     * Returns the path of the shader saved this frame.
+     */
+    pub fn show(&mut self, context: &Context, passes: &[(String, String)], errors: &[&ShaderError]) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+
+        let mut saved = None;
+        egui::SidePanel::left("shader_editor")
+            .resizable(true)
+            .default_width(560.0)
+            .show(context, |ui| {
+                self.file_controls(ui, passes, &mut saved);
+                let Some(path) = self.path.clone() else {
+                    ui.label("Select a pass to edit its shader");
+                    return;
+                };
+
+                let errors = errors.iter().filter(|e| e.path == path).collect::<Vec<&&ShaderError>>();
+                for error in &errors {
+                    ui.label(RichText::new(format!("{}: {}", error.location(), error.message)).monospace().color(Color32::LIGHT_RED));
+                }
+
+                self.editor(ui, &path, &errors);
+            });
+
+        // Ctrl+S saves while the editor is open, whichever widget has focus
+        if context.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::S)) {
+            saved = self.save();
+        }
+        saved
+    }
+
+    fn file_controls(&mut self, ui: &mut Ui, passes: &[(String, String)], saved: &mut Option<String>) {
+        ui.horizontal(|ui| {
+            let selected = match &self.path {
+                Some(path) => format!("{}{}", path, if self.modified { " *" } else { "" }),
+                None => "No shader".to_string(),
+            };
+            let mut open = None;
+            ComboBox::from_id_salt("shader")
+                .selected_text(selected)
+                .width(320.0)
+                .show_ui(ui, |ui| {
+                    // Precompiled and built-in shaders can't be edited
+                    let editable = |path: &str| !effects::is_builtin(path) && !matches!(ShaderLanguage::from_path(Path::new(path)), Ok(ShaderLanguage::SpirV));
+                    for (name, path) in passes.iter().filter(|(_, p)| editable(p)) {
+                        if ui.selectable_label(self.path.as_ref() == Some(path), format!("{} - {}", name, path)).clicked() {
+                            open = Some(path.clone());
+                        }
+                    }
+                });
+            if let Some(path) = open {
+                self.load(&path);
+            }
+
+            if ui.add_enabled(self.modified, egui::Button::new("Save")).clicked() {
+                *saved = self.save();
+            }
+            if ui.add_enabled(self.modified, egui::Button::new("Revert")).clicked() {
+                if let Some(path) = self.path.clone() {
+                    self.load(&path);
+                }
+            }
+        });
+    }
+
+    fn editor(&mut self, ui: &mut Ui, path: &str, errors: &[&&ShaderError]) {
+        let language = match ShaderLanguage::from_path(Path::new(path)) {
+            Ok(ShaderLanguage::Wgsl) => "rs",
+            _ => "c",
+        };
+        let lines = errors.iter().filter_map(|e| e.line).collect::<Vec<usize>>();
+        let theme = CodeTheme::from_memory(ui.ctx(), ui.style());
+
+        let mut layouter = |ui: &Ui, text: &str, wrap_width: f32| {
+            let mut job = highlight(ui.ctx(), ui.style(), &theme, text, language);
+            for range in lines.iter().filter_map(|l| line_range(text, *l)) {
+                mark_range(&mut job, range, Color32::from_rgb(96, 24, 24));
+            }
+            job.wrap.max_width = wrap_width;
+            ui.fonts(|f| f.layout_job(job))
+        };
+
+        egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+            let response = ui.add(
+                egui::TextEdit::multiline(&mut self.text)
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(40)
+                    .layouter(&mut layouter)
+            );
+            if response.changed() {
+                self.modified = true;
+            }
+        });
+    }
+
+    fn load(&mut self, path: &str) {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                self.path = Some(path.to_string());
+                self.text = text;
+                self.modified = false;
+            },
+            Err(e) => error!("Failed to open {}: {}", path, e),
+        }
+    }
+
+    fn save(&mut self) -> Option<String> {
+        let path = self.path.clone()?;
+        match fs::write(&path, &self.text) {
+            Ok(()) => {
+                info!("Saved {}", path);
+                self.modified = false;
+                Some(path)
+            },
+            Err(e) => {
+                error!("Failed to save {}: {}", path, e);
+                None
+            },
+        }
+    }
+}
+
+/*
+ * Byte range of the 1-based @line in @text, including its line break
+ */
+pub fn line_range(text: &str, line: usize) -> Option<Range<usize>> {
+    let mut start = 0;
+    for (i, l) in text.split_inclusive('\n').enumerate() {
+        if i + 1 == line {
+            return Some(start..start + l.len());
+        }
+        start += l.len();
+    }
+    None
+}
+
+/*
+ * Give the text in @range of @job a @background, splitting the sections that cross its ends
+ */
+pub fn mark_range(job: &mut LayoutJob, range: Range<usize>, background: Color32) {
+    let mut sections = Vec::with_capacity(job.sections.len() + 2);
+    for section in job.sections.drain(..) {
+        let bounds = section.byte_range.clone();
+        let cuts = [bounds.start, range.start.clamp(bounds.start, bounds.end), range.end.clamp(bounds.start, bounds.end), bounds.end];
+        for cut in cuts.windows(2).filter(|c| c[0] < c[1]) {
+            let mut part = section.clone();
+            part.byte_range = cut[0]..cut[1];
+            if cut[0] > bounds.start {
+                part.leading_space = 0.0;
+            }
+            if range.start <= cut[0] && cut[1] <= range.end {
+                part.format.background = background;
+            }
+            sections.push(part);
+        }
+    }
+    job.sections = sections;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::TextFormat;
+
+    #[test]
+    fn lines_include_their_break() {
+        let text = "first\nsecond\nthird";
+        assert_eq!(line_range(text, 1), Some(0..6));
+        assert_eq!(&text[line_range(text, 2).unwrap()], "second\n");
+    }
+
+    #[test]
+    fn last_line_without_a_break() {
+        let text = "first\nsecond\nthird";
+        assert_eq!(line_range(text, 3), Some(13..18));
+        assert_eq!(&text[line_range(text, 3).unwrap()], "third");
+    }
+
+    #[test]
+    fn crlf_lines() {
+        let text = "first\r\nsecond\r\nthird\r\n";
+        assert_eq!(&text[line_range(text, 2).unwrap()], "second\r\n");
+        assert_eq!(line_range(text, 3), Some(15..22));
+        assert_eq!(line_range(text, 4), None);
+    }
+
+    #[test]
+    fn out_of_range_lines() {
+        assert_eq!(line_range("first\nsecond", 0), None);
+        assert_eq!(line_range("first\nsecond", 3), None);
+        assert_eq!(line_range("", 1), None);
+    }
+
+    /*
+     * Job with one section per part of @parts, for highlighted tokens
+     */
+    fn job(parts: &[&str]) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        for part in parts {
+            job.append(part, 0.0, TextFormat::default());
+        }
+        job
+    }
+
+    fn marked(job: &LayoutJob) -> Vec<(Range<usize>, bool)> {
+        job.sections.iter().map(|s| (s.byte_range.clone(), s.format.background == Color32::RED)).collect()
+    }
+
+    #[test]
+    fn marking_splits_sections() {
+        // "int x;\n" "float y;\n" with the second line marked, crossing the section boundary
+        let mut job = job(&["int x;\nfloat", " y;\n"]);
+        mark_range(&mut job, 7..16, Color32::RED);
+        assert_eq!(marked(&job), vec![(0..7, false), (7..12, true), (12..16, true)]);
+    }
+
+    #[test]
+    fn marking_within_a_section() {
+        let mut job = job(&["one\ntwo\nthree\n"]);
+        let range = line_range(&job.text, 2).unwrap();
+        mark_range(&mut job, range, Color32::RED);
+        assert_eq!(marked(&job), vec![(0..4, false), (4..8, true), (8..14, false)]);
+        // The text itself is unchanged
+        assert_eq!(job.text, "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn marking_outside_the_text_changes_nothing() {
+        let mut job = job(&["one\n", "two"]);
+        mark_range(&mut job, 20..30, Color32::RED);
+        assert_eq!(marked(&job), vec![(0..4, false), (4..7, false)]);
+    }
+}
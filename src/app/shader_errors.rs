@@ -0,0 +1,152 @@
+use std::path::Path;
+use egui::{Color32, Context, RichText};
+use crate::app::pipeline::read_source;
+use crate::Error;
+
+/// Lines of source shown around the offending line
+const CONTEXT_LINES: usize = 2;
+
+/**
+ *  A failed shader compilation, with the source around the line the compiler complained about.
+ */
+pub struct ShaderError {
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String,
+    /// Numbered source lines around `line`
+    source: Vec<(usize, String)>,
+}
+
+impl ShaderError {
+    pub fn new(path: &str, error: &Error) -> ShaderError {
+        let message = match error {
+            Error::Shader(message) => message.clone(),
+            e => e.to_string(),
+        };
+        let line = error_line(&message, path);
+        let source = match (line, read_source(Path::new(path))) {
+            (Some(line), Ok(text)) => source_context(&text, line, CONTEXT_LINES),
+            _ => Vec::new(),
+        };
+
+        ShaderError { path: path.to_string(), line, message, source }
+    }
+
+    pub fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{}", self.path, line),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/*
+ * First line of @path that @message refers to. Both shaderc (`path:12: error`) and naga (`┌─ path:12:5`) put the
+ * line number right after the file name.
+ */
+pub fn error_line(message: &str, path: &str) -> Option<usize> {
+    if path.is_empty() {
+        return None;
+    }
+    message.match_indices(path).find_map(|(i, _)| {
+        let digits = message[i + path.len()..].strip_prefix(':')?
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>();
+        digits.parse().ok()
+    })
+}
+
+/*
+ * The 1-based @line of @text with @context lines before and after it
+ */
+pub fn source_context(text: &str, line: usize, context: usize) -> Vec<(usize, String)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.to_string()))
+        .skip(line.saturating_sub(context + 1))
+        .take_while(|(i, _)| *i <= line + context)
+        .collect()
+}
+
+/*
+ * Window listing all @errors, it stays open until the shaders compile again
+ */
+pub fn show(context: &Context, errors: &[&ShaderError]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    egui::Window::new(RichText::new("Shader errors").color(Color32::LIGHT_RED))
+        .default_width(640.0)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+        .collapsible(true)
+        .show(context, |ui| {
+            egui::ScrollArea::vertical().max_height(480.0).show(ui, |ui| {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        ui.separator();
+                    }
+                    ui.label(RichText::new(error.location()).strong());
+                    ui.label(RichText::new(&error.message).monospace());
+                    for (number, text) in &error.source {
+                        let text = RichText::new(format!("{:>5} | {}", number, text)).monospace();
+                        if Some(*number) == error.line {
+                            ui.label(text.color(Color32::WHITE).background_color(Color32::DARK_RED));
+                        } else {
+                            ui.label(text.weak());
+                        }
+                    }
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::app::pipeline::compile_wgsl;
+
+    #[test]
+    fn shaderc_glsl_line() {
+        let message = "shaders/blur.comp:14: error: 'colour' : undeclared identifier\n\
+            shaders/blur.comp:14: error: '' : compilation terminated\n2 errors generated.";
+        assert_eq!(error_line(message, "shaders/blur.comp"), Some(14));
+    }
+
+    #[test]
+    fn shaderc_hlsl_line() {
+        let message = "shaders/scene.hlsl:7: error: 'float5' : unknown type\n1 error generated.";
+        assert_eq!(error_line(message, "shaders/scene.hlsl"), Some(7));
+        // With a column, as other HLSL compilers report it
+        assert_eq!(error_line("shaders/scene.hlsl:7:12: error: unknown type name 'float5'", "shaders/scene.hlsl"), Some(7));
+    }
+
+    #[test]
+    fn naga_wgsl_line() {
+        let source = "@compute @workgroup_size(1)\nfn main() {\n    let x = 1;\n    let y = undefined_value;\n}\n";
+        let Err(Error::Shader(message)) = compile_wgsl(Path::new("shaders/scene.wgsl"), source, &HashMap::new()) else {
+            panic!("The shader compiled");
+        };
+        assert_eq!(error_line(&message, "shaders/scene.wgsl"), Some(4), "{}", message);
+    }
+
+    #[test]
+    fn messages_without_a_line() {
+        assert_eq!(error_line("Failed to read shaders/a.comp: No such file", "shaders/a.comp"), None);
+        assert_eq!(error_line("shaders/other.comp:3: error", "shaders/a.comp"), None);
+        assert_eq!(error_line("shaders/a.comp:3: error", ""), None);
+    }
+
+    #[test]
+    fn context_around_the_line() {
+        let text = "one\ntwo\nthree\nfour\nfive\nsix";
+        let lines = |line| source_context(text, line, 2).into_iter().map(|(i, _)| i).collect::<Vec<usize>>();
+        assert_eq!(lines(4), vec![2, 3, 4, 5, 6]);
+        assert_eq!(lines(1), vec![1, 2, 3]);
+        assert_eq!(lines(6), vec![4, 5, 6]);
+        assert_eq!(source_context(text, 3, 0), vec![(3, "three".to_string())]);
+        assert!(source_context(text, 20, 2).is_empty());
+    }
+}
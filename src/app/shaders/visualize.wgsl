@@ -0,0 +1,122 @@
+// Writes the visualization of `image` to `target_image`, using the statistics gathered by visualize_stats.wgsl
+// - NUM_IMAGES is provided as a constant by the engine
+
+struct Constants {
+    image: i32,
+    target_image: i32,
+    mode: i32,
+    channel: i32,
+    range: vec2<f32>,
+};
+
+struct Stats {
+    low: array<u32, 4>,
+    high: array<u32, 4>,
+    nan: u32,
+    inf: u32,
+};
+
+// Modes, in the order of VisualizeMode
+const COLOR: i32 = 0;
+const CHANNEL: i32 = 1;
+const HEATMAP: i32 = 2;
+const NORMALIZE: i32 = 3;
+const ABSOLUTE: i32 = 4;
+const NON_FINITE: i32 = 5;
+
+var<push_constant> constants: Constants;
+@group( 0 ) @binding( 0 ) var images: binding_array<texture_storage_2d<rgba8unorm, read_write>, NUM_IMAGES>;
+@group( 0 ) @binding( 1 ) var<storage, read> stats: Stats;
+
+// Inverse of `ordered` in visualize_stats.wgsl
+fn unordered( v: u32 ) -> f32
+{
+    return bitcast<f32>( select( ~v, v & 0x7fffffffu, ( v & 0x80000000u ) != 0u ) );
+}
+
+// Polynomial approximation of the Turbo colormap
+fn turbo( t: f32 ) -> vec3<f32>
+{
+    let x = clamp( t, 0.0, 1.0 );
+    let v4 = vec4<f32>( 1.0, x, x * x, x * x * x );
+    let v2 = v4.zw * v4.z;
+    return vec3<f32>(
+        dot( v4, vec4<f32>( 0.13572138, 4.61539260, -42.66032258, 132.13108234 ) ) + dot( v2, vec2<f32>( -152.94239396, 59.28637943 ) ),
+        dot( v4, vec4<f32>( 0.09140261, 2.19418839, 4.84296658, -14.18503333 ) ) + dot( v2, vec2<f32>( 4.27729857, 2.82956604 ) ),
+        dot( v4, vec4<f32>( 0.10667330, 12.64194608, -60.58204836, 110.36276771 ) ) + dot( v2, vec2<f32>( -89.90310912, 27.34824973 ) )
+    );
+}
+
+fn is_non_finite( v: f32 ) -> bool
+{
+    return ( bitcast<u32>( v ) & 0x7f800000u ) == 0x7f800000u;
+}
+
+fn is_nan( v: f32 ) -> bool
+{
+    return is_non_finite( v ) && ( bitcast<u32>( v ) & 0x007fffffu ) != 0u;
+}
+
+@compute @workgroup_size( 16, 16, 1 )
+fn main( @builtin( global_invocation_id ) id: vec3<u32> )
+{
+    let size = textureDimensions( images[ constants.image ] );
+    if( any( id.xy >= size ) )
+    {
+        return;
+    }
+
+    let value = textureLoad( images[ constants.image ], vec2<i32>( id.xy ) );
+    let v = value[ constants.channel ];
+    var color = vec3<f32>( value.rgb );
+    switch( constants.mode )
+    {
+        case CHANNEL:
+        {
+            color = vec3<f32>( v );
+        }
+        case HEATMAP:
+        {
+            color = turbo( ( v - constants.range.x ) / max( constants.range.y - constants.range.x, 1e-6 ) );
+        }
+        case NORMALIZE:
+        {
+            // A channel without finite values has an empty range
+            for( var c = 0; c < 3; c++ )
+            {
+                let low = unordered( stats.low[ c ] );
+                let high = unordered( stats.high[ c ] );
+                if( stats.low[ c ] <= stats.high[ c ] )
+                {
+                    color[ c ] = ( value[ c ] - low ) / max( high - low, 1e-6 );
+                }
+            }
+        }
+        case ABSOLUTE:
+        {
+            color = abs( value.rgb );
+        }
+        case NON_FINITE:
+        {
+            let nan = any( vec4<bool>( is_nan( value.r ), is_nan( value.g ), is_nan( value.b ), is_nan( value.a ) ) );
+            let infinite = any( vec4<bool>( is_non_finite( value.r ), is_non_finite( value.g ), is_non_finite( value.b ), is_non_finite( value.a ) ) );
+            if( nan )
+            {
+                color = vec3<f32>( 1.0, 0.0, 1.0 );
+            }
+            else if( infinite )
+            {
+                color = vec3<f32>( 0.0, 1.0, 1.0 );
+            }
+            else
+            {
+                color = vec3<f32>( dot( clamp( value.rgb, vec3<f32>( 0.0 ), vec3<f32>( 1.0 ) ), vec3<f32>( 0.2126, 0.7152, 0.0722 ) ) * 0.5 );
+            }
+        }
+        default:
+        {
+        }
+    }
+
+    textureStore( images[ constants.target_image ], vec2<i32>( id.xy ), vec4<f32>( color, 1.0 ) );
+}
@@ -0,0 +1,97 @@
+// Statistics of the visualized image: the range of each channel, the amount of NaN and infinite values and a
+// histogram of each channel over `range`. Every workgroup accumulates its own before adding them to the buffer.
+// - NUM_IMAGES is provided as a constant by the engine
+
+struct Constants {
+    image: i32,
+    target_image: i32,
+    mode: i32,
+    channel: i32,
+    range: vec2<f32>,
+};
+
+struct Stats {
+    low: array<atomic<u32>, 4>,
+    high: array<atomic<u32>, 4>,
+    nan: atomic<u32>,
+    inf: atomic<u32>,
+    bins: array<atomic<u32>, 1024>,
+};
+
+const BINS: u32 = 256u;
+
+var<push_constant> constants: Constants;
+@group( 0 ) @binding( 0 ) var images: binding_array<texture_storage_2d<rgba8unorm, read_write>, NUM_IMAGES>;
+@group( 0 ) @binding( 1 ) var<storage, read_write> stats: Stats;
+
+var<workgroup> bins: array<atomic<u32>, 1024>;
+var<workgroup> low: array<atomic<u32>, 4>;
+var<workgroup> high: array<atomic<u32>, 4>;
+
+// Unsigned integer with the same order as the float @v, so ranges can be found with atomicMin and atomicMax
+fn ordered( v: f32 ) -> u32
+{
+    let bits = bitcast<u32>( v );
+    return select( bits | 0x80000000u, ~bits, ( bits & 0x80000000u ) != 0u );
+}
+
+@compute @workgroup_size( 16, 16, 1 )
+fn main( @builtin( global_invocation_id ) id: vec3<u32>, @builtin( local_invocation_index ) local: u32 )
+{
+    for( var i = local; i < 4u * BINS; i += 256u )
+    {
+        atomicStore( &bins[ i ], 0u );
+    }
+    if( local < 4u )
+    {
+        atomicStore( &low[ local ], 0xffffffffu );
+        atomicStore( &high[ local ], 0u );
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions( images[ constants.image ] );
+    if( all( id.xy < size ) )
+    {
+        let value = textureLoad( images[ constants.image ], vec2<i32>( id.xy ) );
+        for( var c = 0u; c < 4u; c++ )
+        {
+            let v = value[ c ];
+            let bits = bitcast<u32>( v );
+            if( ( bits & 0x7f800000u ) == 0x7f800000u )
+            {
+                if( ( bits & 0x007fffffu ) != 0u )
+                {
+                    atomicAdd( &stats.nan, 1u );
+                }
+                else
+                {
+                    atomicAdd( &stats.inf, 1u );
+                }
+                continue;
+            }
+
+            atomicMin( &low[ c ], ordered( v ) );
+            atomicMax( &high[ c ], ordered( v ) );
+            let t = ( v - constants.range.x ) / max( constants.range.y - constants.range.x, 1e-6 );
+            if( t >= 0.0 && t <= 1.0 )
+            {
+                atomicAdd( &bins[ c * BINS + min( u32( t * f32( BINS ) ), BINS - 1u ) ], 1u );
+            }
+        }
+    }
+    workgroupBarrier();
+
+    for( var i = local; i < 4u * BINS; i += 256u )
+    {
+        let count = atomicLoad( &bins[ i ] );
+        if( count > 0u )
+        {
+            atomicAdd( &stats.bins[ i ], count );
+        }
+    }
+    if( local < 4u )
+    {
+        atomicMin( &stats.low[ local ], atomicLoad( &low[ local ] ) );
+        atomicMax( &stats.high[ local ], atomicLoad( &high[ local ] ) );
+    }
+}
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::Arc;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use cen::graphics::Renderer;
+use cen::vulkan::{Buffer, CommandBuffer};
+use egui::{pos2, vec2, Color32, ComboBox, Context, Sense, Shape, Stroke, Ui};
+use gpu_allocator::MemoryLocation;
+use crate::app::image::{bind_images, ImageFormat, StorageImage};
+use crate::app::pipeline::{compile_wgsl, ComputePipeline, PushDescriptorLayout};
+use crate::Error;
+
+/// Bins of the histogram of each channel
+pub const HISTOGRAM_BINS: usize = 256;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+const STATS_SHADER: &str = include_str!("shaders/visualize_stats.wgsl");
+const VISUALIZE_SHADER: &str = include_str!("shaders/visualize.wgsl");
+
+/**
+ *  How an image is shown on screen, its contents are never changed.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum VisualizeMode {
+    /// The image as rendered
+    Color,
+    /// A single channel in grayscale
+    Channel,
+    /// A single channel in false color, from the start to the end of the range
+    Heatmap,
+    /// Each color channel stretched to the range of its values in the image
+    Normalize,
+    /// Absolute value of the color channels
+    Absolute,
+    /// NaN values in magenta and infinite values in cyan, over a dimmed grayscale image
+    NonFinite,
+    /// The image as rendered, with a histogram of its channels over the range
+    Histogram,
+}
+
+impl VisualizeMode {
+    pub const ALL: [VisualizeMode; 7] = [
+        VisualizeMode::Color,
+        VisualizeMode::Channel,
+        VisualizeMode::Heatmap,
+        VisualizeMode::Normalize,
+        VisualizeMode::Absolute,
+        VisualizeMode::NonFinite,
+        VisualizeMode::Histogram,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VisualizeMode::Color => "Color",
+            VisualizeMode::Channel => "Channel",
+            VisualizeMode::Heatmap => "Heatmap",
+            VisualizeMode::Normalize => "Normalize",
+            VisualizeMode::Absolute => "Absolute value",
+            VisualizeMode::NonFinite => "NaN/Inf",
+            VisualizeMode::Histogram => "Histogram",
+        }
+    }
+
+    /*
+     * Mode as numbered in visualize.wgsl, the histogram is drawn by the GUI over the image in color
+     */
+    fn shader_mode(&self) -> i32 {
+        match self {
+            VisualizeMode::Color | VisualizeMode::Histogram => 0,
+            VisualizeMode::Channel => 1,
+            VisualizeMode::Heatmap => 2,
+            VisualizeMode::Normalize => 3,
+            VisualizeMode::Absolute => 4,
+            VisualizeMode::NonFinite => 5,
+        }
+    }
+
+    pub fn uses_channel(&self) -> bool {
+        matches!(self, VisualizeMode::Channel | VisualizeMode::Heatmap)
+    }
+
+    pub fn uses_range(&self) -> bool {
+        matches!(self, VisualizeMode::Heatmap | VisualizeMode::Histogram)
+    }
+
+    /*
+     * Modes gathering statistics of the image, which takes an extra pass over it
+     */
+    pub fn uses_stats(&self) -> bool {
+        matches!(self, VisualizeMode::Normalize | VisualizeMode::Histogram)
+    }
+
+    /*
+     * Modes showing values only float images hold, 8-bit images are never negative, NaN or infinite
+     */
+    pub fn needs_float_images(&self) -> bool {
+        matches!(self, VisualizeMode::Absolute | VisualizeMode::NonFinite)
+    }
+}
+
+/**
+ *  Visualization of a single image.
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct VisualizeSettings {
+    pub mode: VisualizeMode,
+    /// Channel shown by the channel and heatmap modes, 0 to 3 for RGBA
+    pub channel: usize,
+    /// Values mapped to the ends of the heatmap and histogram
+    pub range: [f32; 2],
+}
+
+impl Default for VisualizeSettings {
+    fn default() -> Self {
+        Self {
+            mode: VisualizeMode::Color,
+            channel: 0,
+            range: [0.0, 1.0],
+        }
+    }
+}
+
+/**
+ *  Push constants of the visualization shaders.
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct VisualizeConstants {
+    image: i32,
+    target_image: i32,
+    mode: i32,
+    channel: i32,
+    range: [f32; 2],
+}
+
+/**
+ *  Statistics as written by visualize_stats.wgsl.
+ */
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct StatsBuffer {
+    low: [u32; 4],
+    high: [u32; 4],
+    nan: u32,
+    inf: u32,
+    bins: [u32; 4 * HISTOGRAM_BINS],
+}
+
+/**
+ *  Statistics of a visualized image.
+ */
+#[derive(Clone, Debug)]
+pub struct ImageStats {
+    /// Smallest and largest finite value of each channel, None when a channel has no finite values
+    pub ranges: [Option<[f32; 2]>; 4],
+    /// Amount of NaN channel values
+    pub nan: u32,
+    /// Amount of infinite channel values
+    pub inf: u32,
+    /// Histogram of each channel over the range of the settings
+    pub bins: [Vec<u32>; 4],
+    pub range: [f32; 2],
+}
+
+/*
+ * Float of @v as mapped to an unsigned integer with the same order by `ordered` in visualize_stats.wgsl
+ */
+pub fn unordered(v: u32) -> f32 {
+    f32::from_bits(if v & 0x8000_0000 != 0 { v & 0x7fff_ffff } else { !v })
+}
+
+/**
+ *  Shaders and resources that render the visualization of an image into an image of its own, which is presented
+ *  instead of the output.
+ */
+pub struct VisualizePass {
+    stats_pipeline: ComputePipeline,
+    pipeline: ComputePipeline,
+    /// Amount of images of the DrawConfig, the visualization is written to the image after them
+    image_count: u32,
+    image: StorageImage,
+    /// Statistics buffer of each frame in flight, with the range its histogram covers once written
+    stats: Vec<(Buffer, Option<[f32; 2]>)>,
+}
+
+impl VisualizePass {
+    /*
+     * Pass for a DrawConfig with @image_count images of @width by @height in @format
+     */
+    pub fn new(renderer: &mut Renderer, image_count: u32, width: u32, height: u32, format: ImageFormat) -> Result<VisualizePass, Error> {
+        let layout_bindings = &[
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(image_count + 1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        ];
+        let layout = Arc::new(PushDescriptorLayout::new(&renderer.device, layout_bindings)?);
+        let push_constant_ranges = [
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(size_of::<VisualizeConstants>() as u32),
+        ];
+        let macros = HashMap::from([ ("NUM_IMAGES".to_string(), (image_count + 1).to_string()) ]);
+        // WGSL has no macros for types, the shaders are written for rgba8 images
+        let stats_code = compile_wgsl(Path::new("visualize_stats.wgsl"), &STATS_SHADER.replace("rgba8unorm", format.wgsl()), &macros)?;
+        let code = compile_wgsl(Path::new("visualize.wgsl"), &VISUALIZE_SHADER.replace("rgba8unorm", format.wgsl()), &macros)?;
+
+        let stats = (0..renderer.swapchain.get_image_count())
+            .map(|_| (Buffer::new(
+                &renderer.device,
+                &mut renderer.allocator,
+                MemoryLocation::GpuToCpu,
+                size_of::<StatsBuffer>() as vk::DeviceSize,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST
+            ), None))
+            .collect();
+
+        Ok(Self {
+            stats_pipeline: ComputePipeline::new(&renderer.device, &stats_code, &layout, &push_constant_ranges, &[])?,
+            pipeline: ComputePipeline::new(&renderer.device, &code, &layout, &push_constant_ranges, &[])?,
+            image_count,
+            image: StorageImage::new(&renderer.device, &renderer.allocator, width, height, format, vk::ImageUsageFlags::TRANSFER_SRC)?,
+            stats,
+        })
+    }
+
+    /*
+     * Whether the pass fits a DrawConfig with @image_count images of @width by @height in @format
+     */
+    pub fn matches(&self, image_count: u32, width: u32, height: u32, format: ImageFormat) -> bool {
+        self.image_count == image_count && self.image.width == width && self.image.height == height && self.image.format == format
+    }
+
+    /*
+     * Statistics written by the previous use of the @frame slot, that frame is no longer in flight
+     */
+    pub fn read(&mut self, frame: usize) -> Option<ImageStats> {
+        let (buffer, range) = &mut self.stats[frame];
+        let range = range.take()?;
+        let stats = bytemuck::pod_read_unaligned::<StatsBuffer>(&buffer.mapped()[..size_of::<StatsBuffer>()]);
+        Some(ImageStats {
+            ranges: std::array::from_fn(|c| (stats.low[c] <= stats.high[c]).then(|| [unordered(stats.low[c]), unordered(stats.high[c])])),
+            nan: stats.nan,
+            inf: stats.inf,
+            bins: std::array::from_fn(|c| stats.bins[c * HISTOGRAM_BINS..(c + 1) * HISTOGRAM_BINS].to_vec()),
+            range,
+        })
+    }
+
+    /*
+     * Record the visualization of @images[@image] with @settings in the @frame slot, after the passes that write it.
+     * Statistics are only gathered for the modes that use them. Returns the image to present, left in the GENERAL
+     * layout.
+     */
+    pub fn record(&mut self, renderer: &Renderer, command_buffer: &mut CommandBuffer, images: &[&StorageImage], image: usize, settings: &VisualizeSettings, frame: usize) -> &StorageImage {
+        let device = renderer.device.handle();
+        let (buffer, range) = &mut self.stats[frame];
+        let uses_stats = settings.mode.uses_stats();
+        *range = uses_stats.then_some(settings.range);
+
+        // Ranges start out empty, the other statistics at zero. The barrier also makes the writes of the passes to
+        // the image visible.
+        let low_size = (size_of::<[u32; 4]>()) as vk::DeviceSize;
+        unsafe {
+            if uses_stats {
+                device.cmd_fill_buffer(command_buffer.handle(), *buffer.handle(), 0, low_size, u32::MAX);
+                device.cmd_fill_buffer(command_buffer.handle(), *buffer.handle(), low_size, vk::WHOLE_SIZE, 0);
+            }
+            device.cmd_pipeline_barrier(
+                command_buffer.handle(),
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE | vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)],
+                &[],
+                &[]
+            );
+        }
+        // The previous visualization is no longer needed once the previous frame presented it
+        renderer.transition_image(
+            command_buffer,
+            self.image.handle(),
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::NONE,
+            vk::AccessFlags::SHADER_WRITE
+        );
+
+        let mut bound = images.to_vec();
+        bound.push(&self.image);
+        let constants = VisualizeConstants {
+            image: image as i32,
+            target_image: self.image_count as i32,
+            mode: settings.mode.shader_mode(),
+            channel: settings.channel as i32,
+            range: settings.range,
+        };
+        let groups = [self.image.width.div_ceil(WORKGROUP_SIZE), self.image.height.div_ceil(WORKGROUP_SIZE)];
+        let pipelines = if uses_stats { vec![&self.stats_pipeline, &self.pipeline] } else { vec![&self.pipeline] };
+        for pipeline in pipelines {
+            command_buffer.bind_pipeline(pipeline);
+            command_buffer.push_constants(pipeline, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&constants));
+            bind_images(command_buffer, pipeline, &bound);
+            command_buffer.bind_push_descriptor(
+                pipeline,
+                0,
+                &[vk::WriteDescriptorSet::default()
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&[buffer.binding()])
+                ]
+            );
+            command_buffer.dispatch(groups[0], groups[1], 1);
+
+            // The visualization reads the statistics, the host reads them once the frame is done
+            unsafe {
+                device.cmd_pipeline_barrier(
+                    command_buffer.handle(),
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::HOST,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier::default()
+                        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                        .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::HOST_READ)],
+                    &[],
+                    &[]
+                );
+            }
+        }
+
+        &self.image
+    }
+}
+
+/**
+ *  Selects the image shown on screen and how it's visualized, each image keeps its own settings.
+ */
+pub struct Visualizer {
+    /// Image shown, None for the output
+    image: Option<usize>,
+    settings: Vec<VisualizeSettings>,
+    stats: Option<ImageStats>,
+    log_histogram: bool,
+}
+
+impl Default for Visualizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visualizer {
+    pub fn new() -> Visualizer {
+        Self {
+            image: None,
+            settings: Vec::new(),
+            stats: None,
+            log_histogram: false,
+        }
+    }
+
+    /*
+     * The image to visualize out of @image_count and its settings, None when the output is shown as rendered. Modes
+     * that need float images show 8-bit images in color.
+     */
+    pub fn current(&mut self, image_count: usize, float_images: bool) -> Option<(usize, VisualizeSettings)> {
+        let image = self.shown(image_count)?;
+        let mut settings = self.settings[image];
+        if settings.mode.needs_float_images() && !float_images {
+            settings.mode = VisualizeMode::Color;
+        }
+        if self.image.is_none() && settings.mode == VisualizeMode::Color {
+            return None;
+        }
+        Some((image, settings))
+    }
+
+    /*
+     * Index of the shown image out of @image_count, the output is the last image
+     */
+    fn shown(&mut self, image_count: usize) -> Option<usize> {
+        self.settings.resize(image_count, VisualizeSettings::default());
+        Some(self.image.filter(|i| *i < image_count).unwrap_or(image_count.checked_sub(1)?))
+    }
+
+    pub fn set_stats(&mut self, stats: ImageStats) {
+        self.stats = Some(stats);
+    }
+
+    /*
+     * Menu to pick the shown image out of @images, given by name, and its visualization. Without @float_images the
+     * modes for values outside [0, 1] are disabled.
+     */
+    pub fn menu(&mut self, ui: &mut Ui, images: &[String], float_images: bool) {
+        let Some(image) = self.shown(images.len()) else {
+            return;
+        };
+
+        let selected = match self.image {
+            Some(i) if i < images.len() => images[i].clone(),
+            _ => "Output".to_string(),
+        };
+        ComboBox::from_label("Image")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.image, None, "Output");
+                for (i, name) in images.iter().enumerate() {
+                    ui.selectable_value(&mut self.image, Some(i), name);
+                }
+            });
+
+        let settings = &mut self.settings[image];
+        ComboBox::from_label("View")
+            .selected_text(settings.mode.name())
+            .show_ui(ui, |ui| {
+                for mode in VisualizeMode::ALL {
+                    ui.add_enabled_ui(float_images || !mode.needs_float_images(), |ui| {
+                        ui.selectable_value(&mut settings.mode, mode, mode.name())
+                            .on_disabled_hover_text("8-bit images are never negative, NaN or infinite, needs a float image format in the DrawConfig");
+                    });
+                }
+            });
+        ui.add_enabled_ui(settings.mode.uses_channel(), |ui| {
+            ui.horizontal(|ui| {
+                for (i, name) in ["R", "G", "B", "A"].iter().enumerate() {
+                    ui.selectable_value(&mut settings.channel, i, *name);
+                }
+            });
+        });
+        ui.add_enabled_ui(settings.mode.uses_range(), |ui| {
+            ui.horizontal(|ui| {
+                let [min, max] = &mut settings.range;
+                ui.add(egui::DragValue::new(min).speed(0.01).prefix("min: "));
+                ui.add(egui::DragValue::new(max).speed(0.01).prefix("max: "));
+            });
+        });
+
+        // Statistics are only gathered by the modes that use them
+        let stats = self.stats.as_ref().filter(|_| settings.mode.uses_stats());
+        if let Some(stats) = stats {
+            ui.separator();
+            for (name, range) in ["R", "G", "B", "A"].iter().zip(&stats.ranges) {
+                match range {
+                    Some([low, high]) => ui.monospace(format!("{} {:.4} .. {:.4}", name, low, high)),
+                    None => ui.monospace(format!("{} -", name)),
+                };
+            }
+            if float_images {
+                ui.monospace(format!("NaN {}, Inf {}", stats.nan, stats.inf));
+            }
+        }
+    }
+
+    /*
+     * Histogram window of the shown image while it's in the histogram mode
+     */
+    pub fn show(&mut self, context: &Context, image_count: usize) {
+        match self.shown(image_count) {
+            Some(image) if self.settings[image].mode == VisualizeMode::Histogram => {},
+            _ => return,
+        }
+
+        egui::Window::new("Histogram")
+            .default_width(420.0)
+            .show(context, |ui| {
+                let Some(stats) = &self.stats else {
+                    ui.label("No statistics yet");
+                    return;
+                };
+                ui.checkbox(&mut self.log_histogram, "Logarithmic");
+                Self::histogram(ui, stats, self.log_histogram);
+                ui.label(format!("{:.4} .. {:.4}", stats.range[0], stats.range[1]));
+            });
+    }
+
+    fn histogram(ui: &mut Ui, stats: &ImageStats, log: bool) {
+        let (response, painter) = ui.allocate_painter(vec2(ui.available_width(), 160.0), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::from_gray(16));
+
+        let scale = |count: u32| if log { (count as f32 + 1.0).ln() } else { count as f32 };
+        let peak = stats.bins.iter().flatten().map(|c| scale(*c)).fold(1.0, f32::max);
+        let colors = [Color32::RED, Color32::GREEN, Color32::from_rgb(64, 128, 255), Color32::GRAY];
+        for (bins, color) in stats.bins.iter().zip(colors) {
+            let points = bins.iter().enumerate()
+                .map(|(i, count)| pos2(
+                    rect.left() + rect.width() * (i as f32 + 0.5) / HISTOGRAM_BINS as f32,
+                    rect.bottom() - rect.height() * scale(*count) / peak
+                ))
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.0, color)));
+        }
+        painter.rect_stroke(rect, 0.0, Stroke::new(1.0, Color32::DARK_GRAY));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visualizer(image: Option<usize>, mode: VisualizeMode) -> Visualizer {
+        let mut visualizer = Visualizer::new();
+        visualizer.image = image;
+        visualizer.settings = vec![VisualizeSettings { mode, ..VisualizeSettings::default() }; 3];
+        visualizer
+    }
+
+    #[test]
+    fn output_in_color_is_not_visualized() {
+        assert_eq!(visualizer(None, VisualizeMode::Color).current(3, false), None);
+        assert_eq!(visualizer(Some(1), VisualizeMode::Color).current(3, false).map(|(i, _)| i), Some(1));
+        assert_eq!(visualizer(None, VisualizeMode::Heatmap).current(3, false).map(|(i, _)| i), Some(2));
+    }
+
+    #[test]
+    fn float_modes_show_8_bit_images_in_color() {
+        for mode in [VisualizeMode::Absolute, VisualizeMode::NonFinite] {
+            assert_eq!(visualizer(None, mode).current(3, false), None);
+            assert_eq!(visualizer(Some(0), mode).current(3, false).unwrap().1.mode, VisualizeMode::Color);
+            assert_eq!(visualizer(Some(0), mode).current(3, true).unwrap().1.mode, mode);
+        }
+    }
+
+    #[test]
+    fn only_statistics_modes_gather_them() {
+        let modes = VisualizeMode::ALL.iter().filter(|m| m.uses_stats()).copied().collect::<Vec<VisualizeMode>>();
+        assert_eq!(modes, vec![VisualizeMode::Normalize, VisualizeMode::Histogram]);
+    }
+
+    #[test]
+    fn ordering_round_trips() {
+        // Mirrors `ordered` in visualize_stats.wgsl
+        let ordered = |v: f32| {
+            let bits = v.to_bits();
+            if bits & 0x80000000 != 0 { !bits } else { bits | 0x80000000 }
+        };
+        let values = [f32::NEG_INFINITY, -2.5, -0.0, 0.0, 1e-20, 0.5, 3.0, f32::MAX];
+        for pair in values.windows(2) {
+            assert!(ordered(pair[0]) <= ordered(pair[1]));
+        }
+        for v in values {
+            assert_eq!(unordered(ordered(v)), v);
+        }
+    }
+}
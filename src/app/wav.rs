@@ -0,0 +1,20 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use crate::Error;
+
+/*
+ * Write interleaved stereo samples to a 32-bit float wav file
+ */
+pub fn write_wav_audio( in_data: &[f32], sample_rate: u32, path: &str ) -> Result<(), Error> {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    let mut writer = WavWriter::create(path, spec)?;
+    for sample in in_data {
+        writer.write_sample(*sample)?;
+    }
+    Ok(writer.finalize()?)
+}
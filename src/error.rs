@@ -0,0 +1,102 @@
+use std::{fmt, io};
+use cen::vulkan::PipelineErr;
+
+/**
+ *  Errors surfaced by kiyo's public API.
+ */
+#[derive(Debug)]
+pub enum Error {
+    /// No usable audio output device, or the device rejected the stream
+    AudioDevice(String),
+    /// An audio file could not be decoded
+    Decode(String),
+    /// An image or audio file could not be encoded
+    Encode(String),
+    Io(io::Error),
+    /// A shader could not be found or failed to compile
+    Shader(String),
+    /// The provided configuration is invalid
+    Config(String),
+    /// A GPU resource could not be created
+    Gpu(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::AudioDevice(err) => write!(f, "Audio device error: {}", err),
+            Error::Decode(err) => write!(f, "Decode error: {}", err),
+            Error::Encode(err) => write!(f, "Encode error: {}", err),
+            Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Shader(err) => write!(f, "Shader error: {}", err),
+            Error::Config(err) => write!(f, "Config error: {}", err),
+            Error::Gpu(err) => write!(f, "GPU error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<PipelineErr> for Error {
+    fn from(err: PipelineErr) -> Self {
+        Error::Shader(err.to_string())
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        match err {
+            image::ImageError::IoError(err) => Error::Io(err),
+            err => Error::Encode(err.to_string()),
+        }
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(err: png::EncodingError) -> Self {
+        match err {
+            png::EncodingError::IoError(err) => Error::Io(err),
+            err => Error::Encode(err.to_string()),
+        }
+    }
+}
+
+impl From<hound::Error> for Error {
+    fn from(err: hound::Error) -> Self {
+        match err {
+            hound::Error::IoError(err) => Error::Io(err),
+            err => Error::Encode(err.to_string()),
+        }
+    }
+}
+
+impl From<rodio::decoder::DecoderError> for Error {
+    fn from(err: rodio::decoder::DecoderError) -> Self {
+        Error::Decode(err.to_string())
+    }
+}
+
+impl From<rodio::StreamError> for Error {
+    fn from(err: rodio::StreamError) -> Self {
+        Error::AudioDevice(err.to_string())
+    }
+}
+
+impl From<rodio::PlayError> for Error {
+    fn from(err: rodio::PlayError) -> Self {
+        Error::AudioDevice(err.to_string())
+    }
+}
@@ -1,2 +1,4 @@
+pub mod app;
+pub mod error;
 
-pub mod app;
\ No newline at end of file
+pub use self::error::Error;